@@ -0,0 +1,275 @@
+use std::convert::Infallible;
+use std::env;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use defmt_decoder::{DecodeError, Table};
+use embedded_graphics::prelude::Point;
+use serialport::SerialPort;
+use twelve_projects_of_codemas::dashboard::{Dashboard, Readings};
+use twelve_projects_of_codemas::display::{Flush, FontSize, Screen};
+use twelve_projects_of_codemas::effects::{Animator, Effect};
+use twelve_projects_of_codemas::framebuffer::{FrameBuffer, FrameBuffer128x32, FrameBuffer128x64};
+use twelve_projects_of_codemas::matrix::{Layout, Marquee, Matrix, Wiring};
+use twelve_projects_of_codemas::protocol::{Decoder, Message, Packet, MAX_FRAME};
+use twelve_projects_of_codemas::screens;
+use twelve_projects_of_codemas::strip::{Frame, Strip};
+use twelve_projects_of_codemas::ws2812::{self, Chipset};
+
+fn usage() -> ! {
+    eprintln!("usage: codemas-host <port> [led <index> <on|off>]");
+    eprintln!("       codemas-host log <port> <elf>");
+    eprintln!("       codemas-host render <out.png> [128x32|128x64] [day11|dashboard]");
+    eprintln!("       codemas-host effect <rainbow|chase|twinkle|fire|comet|gradient|breathing> [leds]");
+    eprintln!("       codemas-host matrix <text> [8|16]");
+    eprintln!("       codemas-host ws2812 [ws2812b|sk6812|ws2811|ws2811-slow] [clock_mhz] [divisor_int divisor_frac]");
+    std::process::exit(1);
+}
+
+fn open(path: &str) -> Box<dyn SerialPort> {
+    serialport::new(path, 115_200)
+        .timeout(Duration::from_millis(100))
+        .open()
+        .expect("failed to open serial port")
+}
+
+// Reads from the port forever, handing each chunk to `f`.
+fn read_loop(port: &mut Box<dyn SerialPort>, mut f: impl FnMut(&[u8])) -> ! {
+    let mut buf = [0u8; 64];
+    loop {
+        match port.read(&mut buf) {
+            Ok(count) => f(&buf[..count]),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+            Err(e) => panic!("read failed: {e}"),
+        }
+    }
+}
+
+fn log(args: &[String]) -> ! {
+    let (path, elf) = match args {
+        [path, elf] => (path, elf),
+        _ => usage(),
+    };
+
+    let elf = std::fs::read(elf).expect("failed to read elf");
+    let table = Table::parse(&elf)
+        .expect("failed to parse defmt data")
+        .expect("elf contains no defmt data");
+    let mut decoder = table.new_stream_decoder();
+
+    let mut port = open(path);
+    read_loop(&mut port, |bytes| {
+        decoder.received(bytes);
+        loop {
+            match decoder.decode() {
+                Ok(frame) => println!("{}", frame.display(true)),
+                Err(DecodeError::UnexpectedEof) => break,
+                Err(DecodeError::Malformed) => eprintln!("malformed frame, skipping"),
+            }
+        }
+    })
+}
+
+fn draw<D: Flush>(name: &str, target: D) -> D
+where
+    D::Error: std::fmt::Debug,
+{
+    let mut screen = Screen::new(target);
+    match name {
+        "day11" => {
+            screens::day11_title(&mut screen).unwrap();
+            screens::day11_gauge(&mut screen, 60).unwrap();
+        }
+        "dashboard" => {
+            // A slowly warming room, someone walking past the PIR.
+            let mut dashboard = Dashboard::new();
+            for i in 0..40 {
+                dashboard.record_temperature(Some(20.0 + i as f32 * 0.05 + (i % 3) as f32 * 0.1));
+            }
+            dashboard.update(Readings { light: 72, potentiometer: 35, motion: true, beam_broken: false });
+            dashboard.draw(&mut screen).unwrap();
+        }
+        _ => usage(),
+    }
+    screen.release()
+}
+
+fn save_png<const W: usize, const H: usize>(fb: &FrameBuffer<W, H>, path: &str) {
+    let mut png = Vec::new();
+    fb.write_png(|bytes| png.extend_from_slice(bytes));
+    std::fs::write(path, png).expect("failed to write png");
+}
+
+// Draws one of the screens into a simulated panel and saves it, so UI changes
+// can be looked at without flashing a board.
+fn render(args: &[String]) -> ! {
+    let path = args.first().unwrap_or_else(|| usage());
+    let name = args.get(2).map_or("day11", String::as_str);
+    match args.get(1).map(String::as_str) {
+        None | Some("128x32") => save_png(&draw(name, FrameBuffer128x32::new()), path),
+        Some("128x64") => save_png(&draw(name, FrameBuffer128x64::new()), path),
+        _ => usage(),
+    }
+    std::process::exit(0);
+}
+
+// Plays an LED effect in the terminal, one coloured block per pixel.
+fn effect(args: &[String]) -> ! {
+    let effect = match args.first().map(String::as_str) {
+        Some("rainbow") => Effect::Rainbow,
+        Some("chase") => Effect::TheaterChase((255, 180, 0).into()),
+        Some("twinkle") => Effect::Twinkle((255, 255, 255).into()),
+        Some("fire") => Effect::Fire,
+        Some("comet") => Effect::Comet((0, 120, 255).into()),
+        Some("gradient") => Effect::Gradient((255, 0, 80).into(), (0, 80, 255).into()),
+        Some("breathing") => Effect::Breathing((255, 20, 147).into()),
+        _ => usage(),
+    };
+    let leds = args.get(1).map_or(Some(15), |s| s.parse().ok()).unwrap_or_else(|| usage());
+
+    let mut animator = Animator::new(effect);
+    let mut pixels = vec![Default::default(); leds];
+    let start = Instant::now();
+    loop {
+        animator.render(start.elapsed().as_millis() as u32, &mut pixels);
+        let mut line = String::from("\r");
+        for pixel in &pixels {
+            write!(line, "\x1b[48;2;{};{};{}m  ", pixel.r, pixel.g, pixel.b).unwrap();
+        }
+        print!("{line}\x1b[0m");
+        std::io::stdout().flush().unwrap();
+        std::thread::sleep(Duration::from_millis(33));
+    }
+}
+
+// A strip that only exists in memory, for previews.
+struct Preview<const N: usize> {
+    frame: Frame<N>,
+}
+
+impl<const N: usize> Strip<N> for Preview<N> {
+    type Error = Infallible;
+
+    fn frame(&self) -> &Frame<N> {
+        &self.frame
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame<N> {
+        &mut self.frame
+    }
+
+    fn write(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+// Scrolls text across a square LED panel in the terminal.
+fn matrix(args: &[String]) -> ! {
+    let text = args.first().unwrap_or_else(|| usage());
+    let (side, size) = match args.get(1).map(String::as_str) {
+        None | Some("8") => (8, FontSize::Tiny),
+        Some("16") => (16, FontSize::Small),
+        _ => usage(),
+    };
+
+    let layout = Layout::new(side, side, Wiring::SerpentineRows);
+    let mut matrix: Matrix<_, 256> = Matrix::new(Preview { frame: Frame::new() }, layout);
+    let mut marquee = Marquee::new(text, size);
+    loop {
+        marquee.draw(&mut matrix.ink((255, 40, 0).into())).unwrap();
+        marquee.step(side);
+        let mut screen = String::new();
+        for y in 0..side as i32 {
+            for x in 0..side as i32 {
+                let pixel = matrix.pixel(Point::new(x, y)).unwrap();
+                write!(screen, "\x1b[48;2;{};{};{}m  ", pixel.r, pixel.g, pixel.b).unwrap();
+            }
+            screen.push_str("\x1b[0m\n");
+        }
+        print!("{screen}\x1b[{side}A");
+        std::io::stdout().flush().unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+    }
+}
+
+// Runs the WS2812 PIO program for a chipset in the simulator and checks
+// the pulses against the datasheet. The divisor is worked out from the
+// clock like the firmware does unless one is given.
+fn ws2812_timing(args: &[String]) -> ! {
+    let chipset = match args.first().map(String::as_str) {
+        None | Some("ws2812b") => Chipset::Ws2812b,
+        Some("sk6812") => Chipset::Sk6812Rgbw,
+        Some("ws2811") => Chipset::Ws2811,
+        Some("ws2811-slow") => Chipset::Ws2811Slow,
+        _ => usage(),
+    };
+    let arg = |i: usize, default| args.get(i).map_or(Some(default), |s| s.parse().ok()).unwrap_or_else(|| usage());
+    let clock_hz = arg(1, 125) * 1_000_000;
+    let divisor = match chipset.clock_divisor(clock_hz) {
+        Some(best) if args.len() < 3 => {
+            println!("divisor {} + {}/256, bit rate off by {} ppm", best.int, best.frac, best.error_ppm);
+            best.fixed_point()
+        }
+        None if args.len() < 3 => {
+            eprintln!("no divisor gets {} Hz from a {clock_hz} Hz clock", chipset.bit_rate());
+            std::process::exit(1);
+        }
+        _ => (arg(2, 0) as u16, arg(3, 0) as u8),
+    };
+
+    let timing = ws2812::measure(chipset, clock_hz, divisor).unwrap_or_else(|e| {
+        eprintln!("simulation failed: {e:?}");
+        std::process::exit(1);
+    });
+    let limits = chipset.limits();
+    for (name, (min, max), (low, high)) in [
+        ("T0H", timing.t0h, limits.t0h),
+        ("T1H", timing.t1h, limits.t1h),
+        ("period", timing.period, limits.period),
+    ] {
+        let ok = if low <= min && max <= high { "ok" } else { "OUT OF SPEC" };
+        println!("{name:<7}{min:>5}-{max:<5} ns  (datasheet {low}-{high})  {ok}");
+    }
+    std::process::exit(if timing.check(chipset).is_ok() { 0 } else { 1 });
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let path = args.first().unwrap_or_else(|| usage());
+
+    match path.as_str() {
+        "log" => log(&args[1..]),
+        "render" => render(&args[1..]),
+        "effect" => effect(&args[1..]),
+        "matrix" => matrix(&args[1..]),
+        "ws2812" => ws2812_timing(&args[1..]),
+        _ => {}
+    }
+
+    let mut port = open(path);
+
+    if let Some("led") = args.get(1).map(String::as_str) {
+        let index = args.get(2).and_then(|s| s.parse().ok()).unwrap_or_else(|| usage());
+        let on = match args.get(3).map(String::as_str) {
+            Some("on") => true,
+            Some("off") => false,
+            _ => usage(),
+        };
+
+        let mut frame = [0u8; MAX_FRAME];
+        let n = Packet::new(0, Message::Led { index, on }).encode(&mut frame).unwrap();
+        port.write_all(&frame[..n]).expect("failed to write frame");
+    }
+
+    let mut decoder = Decoder::new();
+    read_loop(&mut port, |bytes| {
+        for &byte in bytes {
+            match decoder.push(byte) {
+                Some(Ok(packet)) => println!("#{:<3} {:?}", packet.seq, packet.message),
+                Some(Err(e)) => eprintln!("bad frame: {e:?}"),
+                None => {}
+            }
+        }
+    })
+}
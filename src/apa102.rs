@@ -0,0 +1,152 @@
+// APA102 and SK9822 strips, which have separate clock and data lines and
+// so can be driven by any SPI peripheral.
+//
+// A frame is 32 zero bits, then 32 bits per pixel: three ones, a 5 bit
+// global brightness and the colour in blue, green, red order. After that
+// the strip needs more clock edges to push the data along to the last
+// pixel, one per two pixels. SK9822s also want a 32 bit reset frame after
+// the pixels, and latch on it, so they're sent zeros where APA102s get
+// ones.
+//
+// Brightness goes into the colour like the other strips rather than into
+// the global brightness, which SK9822s apply differently.
+
+use embedded_hal::blocking::spi::Write;
+
+use crate::strip::{Frame, Strip};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Apa102,
+    Sk9822,
+}
+
+pub struct Apa102Strip<SPI, const N: usize> {
+    spi: SPI,
+    variant: Variant,
+    frame: Frame<N>,
+}
+
+impl<SPI, const N: usize> Apa102Strip<SPI, N> {
+    /// Drives a strip of `N` pixels through `spi`, which should be set up
+    /// for mode 0.
+    pub fn new(spi: SPI, variant: Variant) -> Self {
+        Self { spi, variant, frame: Frame::new() }
+    }
+
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+}
+
+impl<SPI: Write<u8>, const N: usize> Strip<N> for Apa102Strip<SPI, N> {
+    type Error = SPI::Error;
+
+    fn frame(&self) -> &Frame<N> {
+        &self.frame
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame<N> {
+        &mut self.frame
+    }
+
+    fn write(&mut self) -> Result<(), SPI::Error> {
+        self.spi.write(&[0; 4])?;
+        for pixel in self.frame.corrected() {
+            self.spi.write(&[0xE0 | 31, pixel.b, pixel.g, pixel.r])?;
+        }
+
+        let end = match self.variant {
+            Variant::Apa102 => 0xFF,
+            Variant::Sk9822 => {
+                self.spi.write(&[0; 4])?;
+                0x00
+            }
+        };
+        for _ in 0..N.div_ceil(16).max(1) {
+            self.spi.write(&[end])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::convert::Infallible;
+    use rgb::RGB8;
+    use std::vec::Vec;
+
+    // An SPI bus that keeps everything written to it.
+    #[derive(Default)]
+    struct Recorder(Vec<u8>);
+
+    impl Write<u8> for Recorder {
+        type Error = Infallible;
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+            self.0.extend_from_slice(words);
+            Ok(())
+        }
+    }
+
+    fn sent<const N: usize>(variant: Variant, pixels: [RGB8; N]) -> Vec<u8> {
+        let mut strip: Apa102Strip<Recorder, N> = Apa102Strip::new(Recorder::default(), variant);
+        *strip.pixels_mut() = pixels;
+        strip.write().unwrap();
+        strip.release().0
+    }
+
+    #[test]
+    fn apa102_frame() {
+        let bytes = sent(Variant::Apa102, [RGB8::new(1, 2, 3), RGB8::new(0xAA, 0xBB, 0xCC)]);
+        let expected = [
+            0x00, 0x00, 0x00, 0x00, // start
+            0xFF, 3, 2, 1, // blue, green, red
+            0xFF, 0xCC, 0xBB, 0xAA,
+            0xFF, // end, one byte covers 16 pixels
+        ];
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn sk9822_frame() {
+        let bytes = sent(Variant::Sk9822, [RGB8::new(1, 2, 3)]);
+        let expected = [
+            0x00, 0x00, 0x00, 0x00, // start
+            0xFF, 3, 2, 1,
+            0x00, 0x00, 0x00, 0x00, // reset
+            0x00, // end
+        ];
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn end_bytes_follow_the_length() {
+        fn end_len<const N: usize>(variant: Variant) -> usize {
+            let reset = if variant == Variant::Sk9822 { 4 } else { 0 };
+            sent(variant, [RGB8::default(); N]).len() - 4 - 4 * N - reset
+        }
+        assert_eq!(end_len::<1>(Variant::Apa102), 1);
+        assert_eq!(end_len::<16>(Variant::Apa102), 1);
+        assert_eq!(end_len::<17>(Variant::Apa102), 2);
+        assert_eq!(end_len::<60>(Variant::Apa102), 4);
+        assert_eq!(end_len::<60>(Variant::Sk9822), 4);
+
+        let bytes = sent(Variant::Apa102, [RGB8::default(); 60]);
+        assert!(bytes[4 + 4 * 60..].iter().all(|&byte| byte == 0xFF));
+        let bytes = sent(Variant::Sk9822, [RGB8::default(); 60]);
+        assert!(bytes[4 + 4 * 60..].iter().all(|&byte| byte == 0x00));
+    }
+
+    #[test]
+    fn brightness_goes_into_the_colour() {
+        let mut strip: Apa102Strip<Recorder, 1> = Apa102Strip::new(Recorder::default(), Variant::Apa102);
+        strip.fill(RGB8::new(255, 255, 255));
+        strip.set_brightness(128);
+        strip.write().unwrap();
+        assert_eq!(strip.release().0[4..8], [0xFF, 128, 128, 128]);
+    }
+}
@@ -0,0 +1,276 @@
+// Counting things going through break beams, from timestamped edges.
+//
+// Polling a beam misses anything quicker than the poll, so day10 takes the
+// beam pins' edge interrupts, timestamps them with the timer and hands
+// them to a `BeamCounter`, which does the rest without caring about the
+// hardware.
+//
+// Each time a beam is broken and then clear again is a `Pass`, and passes
+// of the first beam are what gets counted. Breaks shorter than the glitch
+// filter are ignored. With a second beam a little way past the first, the
+// order they break in gives the direction, and the time between them the
+// speed. Since a break's length is only known once the beam clears, that's
+// when a transit is reported.
+
+/// One object going through one beam.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pass {
+    pub beam: usize,
+    /// When the beam was broken, µs.
+    pub at_us: u64,
+    /// How long it was broken for.
+    pub duration_us: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Through the first beam, then the second.
+    Forward,
+    Backward,
+}
+
+/// One object going through both beams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transit {
+    pub direction: Direction,
+    /// When the second beam was broken, µs.
+    pub at_us: u64,
+    /// Time from breaking one beam to breaking the other.
+    pub gap_us: u64,
+    pub speed_mm_s: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Passed(Pass),
+    Transit(Transit),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BeamCounter {
+    // Distance between the beams, `None` with only one.
+    spacing_mm: Option<u32>,
+    min_break_us: u64,
+    broken_at: [Option<u64>; 2],
+    // The last beam gone through and when it broke, waiting for the
+    // other one.
+    first: Option<(usize, u64)>,
+    count: u32,
+    forward: u32,
+    backward: u32,
+    last_pass: Option<Pass>,
+    last_transit: Option<Transit>,
+}
+
+impl BeamCounter {
+    /// One beam, ignoring breaks shorter than `min_break_us`.
+    pub const fn new(min_break_us: u64) -> Self {
+        Self {
+            spacing_mm: None,
+            min_break_us,
+            broken_at: [None; 2],
+            first: None,
+            count: 0,
+            forward: 0,
+            backward: 0,
+            last_pass: None,
+            last_transit: None,
+        }
+    }
+
+    /// Two beams `spacing_mm` apart along the direction of travel.
+    pub const fn pair(spacing_mm: u32, min_break_us: u64) -> Self {
+        let mut counter = Self::new(min_break_us);
+        counter.spacing_mm = Some(spacing_mm);
+        counter
+    }
+
+    /// Objects that have gone all the way through the first beam.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Transits each way, with two beams.
+    pub fn transits(&self) -> (u32, u32) {
+        (self.forward, self.backward)
+    }
+
+    pub fn last_pass(&self) -> Option<Pass> {
+        self.last_pass
+    }
+
+    pub fn last_transit(&self) -> Option<Transit> {
+        self.last_transit
+    }
+
+    pub fn is_broken(&self, beam: usize) -> bool {
+        self.broken_at.get(beam).is_some_and(Option::is_some)
+    }
+
+    pub fn reset(&mut self) {
+        *self = match self.spacing_mm {
+            Some(spacing_mm) => Self::pair(spacing_mm, self.min_break_us),
+            None => Self::new(self.min_break_us),
+        };
+    }
+
+    /// Takes a beam being broken or clearing at `at_us`. Repeats of the
+    /// state a beam is already in are ignored, as are beams past the
+    /// second. A break only counts once the beam clears and it's known to
+    /// have been long enough, so that's when a `Transit` comes, in place of
+    /// the `Passed` for the second beam.
+    pub fn edge(&mut self, beam: usize, broken: bool, at_us: u64) -> Option<Event> {
+        let state = self.broken_at.get_mut(beam)?;
+        match (broken, *state) {
+            (true, None) => {
+                *state = Some(at_us);
+                None
+            }
+            (false, Some(since)) => {
+                *state = None;
+                let duration_us = at_us.saturating_sub(since);
+                if duration_us < self.min_break_us {
+                    return None;
+                }
+                let pass = Pass { beam, at_us: since, duration_us };
+                if beam == 0 {
+                    self.count += 1;
+                }
+                self.last_pass = Some(pass);
+                Some(self.transit(beam, since).map_or(Event::Passed(pass), Event::Transit))
+            }
+            _ => None,
+        }
+    }
+
+    // Notes a beam broken at `at_us` and says if it finished a transit.
+    fn transit(&mut self, beam: usize, at_us: u64) -> Option<Transit> {
+        let spacing_mm = self.spacing_mm?;
+        match self.first.replace((beam, at_us)) {
+            Some((first, since)) if first != beam => {
+                self.first = None;
+                let gap_us = at_us.saturating_sub(since).max(1);
+                let direction = if first == 0 { Direction::Forward } else { Direction::Backward };
+                match direction {
+                    Direction::Forward => self.forward += 1,
+                    Direction::Backward => self.backward += 1,
+                }
+                let speed_mm_s = (spacing_mm as u64 * 1_000_000 / gap_us).min(u32::MAX as u64) as u32;
+                let transit = Transit { direction, at_us, gap_us, speed_mm_s };
+                self.last_transit = Some(transit);
+                Some(transit)
+            }
+            // Either nothing's started through yet, or whatever went
+            // through this beam last backed out, so time from here.
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN_BREAK_US: u64 = 2_000;
+
+    // Breaks `beam` at `at_us` for `duration_us`, returning what the clear
+    // gave.
+    fn pass(counter: &mut BeamCounter, beam: usize, at_us: u64, duration_us: u64) -> Option<Event> {
+        assert_eq!(counter.edge(beam, true, at_us), None);
+        counter.edge(beam, false, at_us + duration_us)
+    }
+
+    #[test]
+    fn counts_passes_of_the_first_beam() {
+        let mut counter = BeamCounter::new(MIN_BREAK_US);
+        for i in 0..3 {
+            let at_us = i * 100_000;
+            let event = pass(&mut counter, 0, at_us, 5_000);
+            assert_eq!(event, Some(Event::Passed(Pass { beam: 0, at_us, duration_us: 5_000 })));
+        }
+        assert_eq!(counter.count(), 3);
+        assert_eq!(counter.last_pass().map(|pass| pass.at_us), Some(200_000));
+        assert_eq!(counter.transits(), (0, 0));
+
+        // Repeats and unknown beams change nothing.
+        assert_eq!(counter.edge(0, false, 300_000), None);
+        assert_eq!(counter.edge(2, true, 300_000), None);
+        assert_eq!(counter.count(), 3);
+
+        counter.reset();
+        assert_eq!(counter.count(), 0);
+        assert_eq!(counter.last_pass(), None);
+    }
+
+    #[test]
+    fn short_breaks_on_the_first_beam_are_ignored() {
+        let mut counter = BeamCounter::pair(50, MIN_BREAK_US);
+        assert_eq!(pass(&mut counter, 0, 0, MIN_BREAK_US - 1), None);
+        assert_eq!(counter.count(), 0);
+
+        // And don't start timing a transit.
+        assert!(matches!(pass(&mut counter, 1, 10_000, 5_000), Some(Event::Passed(_))));
+        assert_eq!(counter.transits(), (0, 0));
+    }
+
+    #[test]
+    fn short_breaks_on_the_second_beam_are_ignored() {
+        let mut counter = BeamCounter::pair(50, MIN_BREAK_US);
+        assert!(matches!(pass(&mut counter, 0, 0, 5_000), Some(Event::Passed(_))));
+
+        // A blip on the second beam while the object's between them.
+        assert_eq!(pass(&mut counter, 1, 20_000, 100), None);
+        assert_eq!(counter.transits(), (0, 0));
+        assert_eq!(counter.last_transit(), None);
+
+        // The real thing is still timed from the first beam.
+        let event = pass(&mut counter, 1, 50_000, 5_000);
+        let transit = Transit { direction: Direction::Forward, at_us: 50_000, gap_us: 50_000, speed_mm_s: 1_000 };
+        assert_eq!(event, Some(Event::Transit(transit)));
+        assert_eq!(counter.transits(), (1, 0));
+    }
+
+    #[test]
+    fn direction_comes_from_the_order_of_the_beams() {
+        let mut counter = BeamCounter::pair(50, MIN_BREAK_US);
+        pass(&mut counter, 0, 0, 5_000);
+        let forward = pass(&mut counter, 1, 10_000, 5_000);
+        assert!(matches!(forward, Some(Event::Transit(Transit { direction: Direction::Forward, .. }))));
+
+        pass(&mut counter, 1, 1_000_000, 5_000);
+        let backward = pass(&mut counter, 0, 1_010_000, 5_000);
+        assert!(matches!(backward, Some(Event::Transit(Transit { direction: Direction::Backward, .. }))));
+
+        assert_eq!(counter.transits(), (1, 1));
+        // Both went through the first beam.
+        assert_eq!(counter.count(), 2);
+    }
+
+    #[test]
+    fn speed_is_spacing_over_the_gap() {
+        let mut counter = BeamCounter::pair(50, MIN_BREAK_US);
+        // Breaking both at once, one long object, still times the breaks.
+        assert_eq!(counter.edge(0, true, 1_000), None);
+        assert_eq!(counter.edge(1, true, 26_000), None);
+        assert!(matches!(counter.edge(0, false, 40_000), Some(Event::Passed(_))));
+        let event = counter.edge(1, false, 60_000);
+        let transit = Transit { direction: Direction::Forward, at_us: 26_000, gap_us: 25_000, speed_mm_s: 2_000 };
+        assert_eq!(event, Some(Event::Transit(transit)));
+        assert_eq!(counter.last_transit(), Some(transit));
+    }
+
+    #[test]
+    fn backing_out_times_from_the_last_pass() {
+        let mut counter = BeamCounter::pair(50, MIN_BREAK_US);
+        // In through the first beam and back out again.
+        pass(&mut counter, 0, 0, 5_000);
+        pass(&mut counter, 0, 500_000, 5_000);
+        assert_eq!(counter.transits(), (0, 0));
+
+        // Then through properly.
+        pass(&mut counter, 0, 1_000_000, 5_000);
+        let event = pass(&mut counter, 1, 1_100_000, 5_000);
+        assert!(matches!(event, Some(Event::Transit(Transit { gap_us: 100_000, speed_mm_s: 500, .. }))));
+        assert_eq!(counter.count(), 3);
+    }
+}
@@ -0,0 +1,135 @@
+/*
+Every sensor on one OLED page, see src/dashboard.rs.
+
+Several days reuse gpio26, so some sensors need moving for this one:
+  OLED             gpio0 (SDA), gpio1 (SCL)   as day11
+  light sensor     gpio26 (ADC0)              as day6
+  potentiometer    gpio27 (ADC1)              as day4
+  PIR              gpio16                     as day7
+  break beam       gpio17                     day10 used gpio26
+  DS18B20          gpio22                     day8 used gpio26
+
+Like day8, needs to be run in release mode otherwise the DS18B20 gives
+OneWireError::UnexpectedResponse.
+*/
+
+#![no_std]
+#![no_main]
+
+use embedded_hal::adc::OneShot;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use one_wire_bus::OneWire;
+use panic_halt as _;
+use rp_pico::Gp0I2C0Sda;
+use rp_pico::Gp1I2C0Scl;
+use rp_pico::entry;
+use rp_pico::hal;
+use rp_pico::hal::I2C;
+use rp_pico::hal::adc::{Adc, AdcPin};
+use rp_pico::hal::pac;
+use rp_pico::hal::prelude::*;
+use twelve_projects_of_codemas::dashboard::{adc_percent, Dashboard, Readings};
+use twelve_projects_of_codemas::display::Screen;
+use twelve_projects_of_codemas::oled::DisplayConfig;
+use twelve_projects_of_codemas::thermometer::Thermometer;
+
+const TICK_MS: u32 = 100;
+// A temperature reading is started at the top of each period and read back
+// halfway through, leaving the DS18B20 plenty of time to convert without
+// holding up the other sensors.
+const TEMPERATURE_PERIOD: u32 = 20;
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let core = pac::CorePeripherals::take().unwrap();
+
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    let clocks = hal::clocks::init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+
+    let sio = hal::Sio::new(pac.SIO);
+
+    let pins = rp_pico::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let sda_pin: Gp0I2C0Sda = pins.gpio0.reconfigure();
+    let scl_pin: Gp1I2C0Scl = pins.gpio1.reconfigure();
+
+    let config = DisplayConfig::default();
+
+    let i2c = I2C::i2c0(
+        pac.I2C0,
+        sda_pin,
+        scl_pin,
+        config.bus_speed,
+        &mut pac.RESETS,
+        clocks.system_clock.freq(),
+    );
+
+    let mut adc = Adc::new(pac.ADC, &mut pac.RESETS);
+    let mut light_pin = AdcPin::new(pins.gpio26.into_floating_input());
+    let mut pot_pin = AdcPin::new(pins.gpio27.into_floating_input());
+
+    let pir_pin = pins.gpio16.into_pull_down_input();
+    // The receiver is open collector and pulls low when the beam is broken.
+    let beam_pin = pins.gpio17.into_pull_up_input();
+
+    let mut one_wire_bus = OneWire::new(hal::gpio::InOutPin::new(pins.gpio22)).unwrap();
+
+    let mut led_pin = pins.gpio18.into_push_pull_output();
+
+    let display = match config.build(i2c) {
+        Ok(display) => display,
+        Err(_) => loop {
+            led_pin.set_high().unwrap();
+            delay.delay_ms(100);
+            led_pin.set_low().unwrap();
+            delay.delay_ms(100);
+        },
+    };
+
+    let mut screen = Screen::new(display);
+    let mut dashboard = Dashboard::new();
+    let mut thermometer = Thermometer::new();
+    let mut tick: u32 = 0;
+
+    loop {
+        if tick == 0 {
+            thermometer.start(&mut one_wire_bus, &mut delay);
+        } else if tick == TEMPERATURE_PERIOD / 2 {
+            dashboard.record_temperature(thermometer.read(&mut one_wire_bus, &mut delay));
+        }
+        tick = (tick + 1) % TEMPERATURE_PERIOD;
+
+        let light: u16 = adc.read(&mut light_pin).unwrap_or(0);
+        let pot: u16 = adc.read(&mut pot_pin).unwrap_or(0);
+        dashboard.update(Readings {
+            light: adc_percent(light),
+            potentiometer: adc_percent(pot),
+            motion: pir_pin.is_high().unwrap(),
+            beam_broken: beam_pin.is_low().unwrap(),
+        });
+
+        dashboard.draw(&mut screen).unwrap();
+        screen.flush().unwrap();
+
+        delay.delay_ms(TICK_MS);
+    }
+}
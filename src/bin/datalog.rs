@@ -0,0 +1,128 @@
+/*
+Records the potentiometer and the RP2040's internal temperature sensor once a
+second. Plugging the board in shows a read-only drive with the readings in
+DATA.CSV. Whenever the host polls the drive and there are newer readings it's
+told the disk has changed, so opening the file again shows them (some hosts
+need the drive remounting first). The log is only kept in RAM, so unplugging
+the board loses it.
+*/
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use embedded_hal::adc::OneShot;
+use panic_halt as _;
+use rp_pico::entry;
+use rp_pico::hal;
+use rp_pico::hal::adc::AdcPin;
+use rp_pico::hal::pac;
+use rp_pico::hal::pac::interrupt;
+use rp_pico::hal::Adc;
+use twelve_projects_of_codemas::datalog::{CsvDisk, DataLog, Record};
+use twelve_projects_of_codemas::protocol::Sensor;
+use twelve_projects_of_codemas::usb_msc::MscClass;
+use usb_device::{class_prelude::*, prelude::*};
+
+type Disk = CsvDisk<2048>;
+
+static USB_DEVICE: Mutex<RefCell<Option<UsbDevice<hal::usb::UsbBus>>>> = Mutex::new(RefCell::new(None));
+static MSC: Mutex<RefCell<Option<MscClass<hal::usb::UsbBus, Disk>>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    let clocks = hal::clocks::init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+
+    let sio = hal::Sio::new(pac.SIO);
+
+    let pins = rp_pico::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut adc = Adc::new(pac.ADC, &mut pac.RESETS);
+    let mut temp_sensor = adc.take_temp_sensor().unwrap();
+    let mut pot_pin = AdcPin::new(pins.gpio27.into_floating_input());
+
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+    let usb_bus: &'static _ = cortex_m::singleton!(: UsbBusAllocator<hal::usb::UsbBus> = usb_bus).unwrap();
+
+    let msc = MscClass::new(usb_bus, CsvDisk::new(DataLog::new()));
+
+    let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .manufacturer("Trevor")
+        .product("Data logger")
+        .serial_number("0")
+        .build();
+
+    cortex_m::interrupt::free(|cs| {
+        USB_DEVICE.borrow(cs).replace(Some(usb_dev));
+        MSC.borrow(cs).replace(Some(msc));
+    });
+
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::USBCTRL_IRQ);
+    }
+
+    let mut last_reading = timer.get_counter();
+
+    loop {
+        let now = timer.get_counter();
+        if (now - last_reading).to_millis() < 1000 {
+            continue;
+        }
+        last_reading = now;
+        let time_ms = now.duration_since_epoch().to_millis() as u32;
+
+        let pot: u16 = adc.read(&mut pot_pin).unwrap_or(0);
+        let raw_temp: u16 = adc.read(&mut temp_sensor).unwrap_or(0);
+        // From the RP2040 datasheet, 27 C reads 0.706 V and the slope is
+        // -1.721 mV per degree. Stored in hundredths of a degree.
+        let volts = raw_temp as f32 * 3.3 / 4096.0;
+        let temp = 27.0 - (volts - 0.706) / 0.001721;
+
+        cortex_m::interrupt::free(|cs| {
+            if let Some(msc) = MSC.borrow(cs).borrow_mut().as_mut() {
+                let log = msc.device().log();
+                log.push(Record { time_ms, sensor: Sensor::Potentiometer, value: pot as i32 });
+                log.push(Record { time_ms, sensor: Sensor::Temperature, value: (temp * 100.0) as i32 });
+            }
+        });
+    }
+}
+
+#[interrupt]
+fn USBCTRL_IRQ() {
+    cortex_m::interrupt::free(|cs| {
+        let mut usb_dev = USB_DEVICE.borrow(cs).borrow_mut();
+        let mut msc = MSC.borrow(cs).borrow_mut();
+        if let (Some(usb_dev), Some(msc)) = (usb_dev.as_mut(), msc.as_mut()) {
+            usb_dev.poll(&mut [msc]);
+        }
+    });
+}
@@ -1,22 +1,41 @@
+/*
+Counts things going through the break beam, see src/beam.rs.
+
+  beam             gpio26
+  second beam      gpio27, optional, BEAM_SPACING_MM past the first
+  beam LED         gpio18, lit while the first beam is broken
+  count LED        gpio19, toggles for every object counted
+  direction LED    gpio20, lit if the last transit was backwards
+
+The receivers are open collector and pull low when the beam is broken.
+Edges are caught by interrupt and timestamped there, see src/input.rs, so
+breaks are timed to the µs however long the main loop takes to get to them.
+*/
+
 #![no_std]
 #![no_main]
 
-use defmt_serial as _;
-use embedded_hal::adc::OneShot;
-use embedded_hal::digital::v2::InputPin;
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
 use embedded_hal::digital::v2::OutputPin;
 use panic_halt as _;
 use rp_pico::entry;
 use rp_pico::hal;
-use rp_pico::hal::adc::AdcPin;
 use rp_pico::hal::pac;
-use rp_pico::hal::prelude::*;
-use rp_pico::hal::Adc;
+use rp_pico::hal::pac::interrupt;
+use twelve_projects_of_codemas::beam::{BeamCounter, Direction};
+use twelve_projects_of_codemas::input::{self, EdgeInputs, EventQueue};
+
+const BEAM_SPACING_MM: u32 = 50;
+// Shorter breaks are noise.
+const MIN_BREAK_US: u64 = 200;
+const QUEUE: usize = 32;
+
+static INPUTS: Mutex<RefCell<Option<EdgeInputs<'static, 2, QUEUE>>>> = Mutex::new(RefCell::new(None));
 
 #[entry]
 fn main() -> ! {
     let mut pac = pac::Peripherals::take().unwrap();
-    let core = pac::CorePeripherals::take().unwrap();
 
     let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
 
@@ -32,7 +51,7 @@ fn main() -> ! {
     .ok()
     .unwrap();
 
-    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
 
     let sio = hal::Sio::new(pac.SIO);
 
@@ -43,18 +62,45 @@ fn main() -> ! {
         &mut pac.RESETS,
     );
 
-    let beam_pin = pins.gpio26.into_pull_down_input();
-    let mut led_pin = pins.gpio18.into_push_pull_output();
+    let mut beam_led = pins.gpio18.into_push_pull_output();
+    let mut count_led = pins.gpio19.into_push_pull_output();
+    let mut direction_led = pins.gpio20.into_push_pull_output();
+
+    let queue = cortex_m::singleton!(: EventQueue<QUEUE> = EventQueue::new()).unwrap();
+    let (sender, mut receiver) = input::split(queue);
+    let mut inputs = EdgeInputs::new(timer, sender);
+    // Beams 0 and 1 as far as the counter's concerned.
+    inputs.add(pins.gpio26.into_pull_up_input());
+    inputs.add(pins.gpio27.into_pull_up_input());
+
+    cortex_m::interrupt::free(|cs| {
+        INPUTS.borrow(cs).replace(Some(inputs));
+    });
+
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0);
+    }
+
+    let mut counter = BeamCounter::pair(BEAM_SPACING_MM, MIN_BREAK_US);
 
     loop {
-        
-        if beam_pin.is_high().unwrap() {
-            led_pin.set_high().unwrap();
-        }
-        else {
-            led_pin.set_low().unwrap();
-        }
+        let event = receiver.wait();
+        // Low is broken. If edges went missing the counter ignores the
+        // ones that now repeat the state it thinks a beam's in.
+        counter.edge(event.input, !event.edge.is_high(), event.at_us);
 
-        delay.delay_ms(100);
+        beam_led.set_state(counter.is_broken(0).into()).unwrap();
+        count_led.set_state((counter.count() % 2 == 1).into()).unwrap();
+        let backward = counter.last_transit().is_some_and(|t| t.direction == Direction::Backward);
+        direction_led.set_state(backward.into()).unwrap();
     }
 }
+
+#[interrupt]
+fn IO_IRQ_BANK0() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(inputs) = INPUTS.borrow(cs).borrow_mut().as_mut() {
+            inputs.service();
+        }
+    });
+}
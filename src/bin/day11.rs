@@ -1,28 +1,18 @@
 #![no_std]
 #![no_main]
 
-use defmt_serial as _;
-use embedded_hal::adc::OneShot;
-use embedded_hal::digital::v2::InputPin;
 use embedded_hal::digital::v2::OutputPin;
-use embedded_hal::spi::Mode;
 use panic_halt as _;
 use rp_pico::Gp0I2C0Sda;
 use rp_pico::Gp1I2C0Scl;
 use rp_pico::entry;
 use rp_pico::hal;
 use rp_pico::hal::I2C;
-use rp_pico::hal::adc::AdcPin;
-use rp_pico::hal::gpio::FunctionI2C;
-use rp_pico::hal::gpio::Pin;
 use rp_pico::hal::pac;
 use rp_pico::hal::prelude::*;
-use rp_pico::hal::Adc;
-use ssd1306::rotation::DisplayRotation;
-use ssd1306::size::DisplaySize128x64;
-use core::fmt::Write;
-use ssd1306::{mode::TerminalMode, prelude::*, I2CDisplayInterface, Ssd1306};
-use hal::fugit::RateExtU32;
+use twelve_projects_of_codemas::display::Screen;
+use twelve_projects_of_codemas::screens;
+use twelve_projects_of_codemas::oled::DisplayConfig;
 
 #[entry]
 fn main() -> ! {
@@ -57,30 +47,43 @@ fn main() -> ! {
     let sda_pin: Gp0I2C0Sda = pins.gpio0.reconfigure();
     let scl_pin: Gp1I2C0Scl = pins.gpio1.reconfigure();
 
+    let config = DisplayConfig::default();
+
     let i2c = I2C::i2c0(
         pac.I2C0,
         sda_pin,
         scl_pin,
-        400.kHz(),
+        config.bus_speed,
         &mut pac.RESETS,
-        125_000_000.Hz(),
+        clocks.system_clock.freq(),
     );
 
     let mut led_pin = pins.gpio18.into_push_pull_output();
 
-    let interface = I2CDisplayInterface::new(i2c);
+    let display = match config.build(i2c) {
+        Ok(display) => display,
+        // No panel answered at 0x3C or 0x3D, check the wiring. Flash the red
+        // LED so it's obvious why the screen is blank.
+        Err(_) => loop {
+            led_pin.set_high().unwrap();
+            delay.delay_ms(100);
+            led_pin.set_low().unwrap();
+            delay.delay_ms(100);
+        },
+    };
 
-    let mut display = Ssd1306::new(
-        interface,
-        DisplaySize128x32,
-        DisplayRotation::Rotate0,
-    ).into_terminal_mode();
-    display.init().unwrap();
-    display.clear().unwrap();
+    let mut screen = Screen::new(display);
+    screens::day11_title(&mut screen).unwrap();
+    screen.flush().unwrap();
 
-    write!(display, "Writing text to the screen!");
+    let mut level: u32 = 0;
 
     loop {
-    
+        // Only the gauge changes, so only its region goes out over I2C.
+        screens::day11_gauge(&mut screen, level).unwrap();
+        screen.flush().unwrap();
+
+        level = (level + 1) % 101;
+        delay.delay_ms(50);
     }
 }
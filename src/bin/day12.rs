@@ -1,33 +1,46 @@
 #![no_std]
 #![no_main]
 
-use defmt_serial as _;
-use embedded_hal::adc::OneShot;
-use embedded_hal::digital::v2::InputPin;
-use embedded_hal::digital::v2::OutputPin;
-use embedded_hal::spi::Mode;
 use panic_halt as _;
-use rp_pico::Gp0I2C0Sda;
-use rp_pico::Gp1I2C0Scl;
+use rgb::RGB8;
 use rp_pico::entry;
 use rp_pico::hal;
-use rp_pico::hal::I2C;
-use rp_pico::hal::Spi;
-use rp_pico::hal::adc::AdcPin;
-use rp_pico::hal::gpio::FunctionI2C;
+use rp_pico::hal::dma::DMAExt;
 use rp_pico::hal::gpio::FunctionPio0;
 use rp_pico::hal::gpio::Pin;
-use rp_pico::hal::gpio::PullDown;
-use rp_pico::hal::gpio::bank0::Gpio19;
 use rp_pico::hal::pac;
+use rp_pico::hal::pio::PIOExt;
 use rp_pico::hal::prelude::*;
-use rp_pico::hal::Adc;
-use ssd1306::rotation::DisplayRotation;
-use ssd1306::size::DisplaySize128x64;
-use core::fmt::Write;
-use ssd1306::{mode::TerminalMode, prelude::*, I2CDisplayInterface, Ssd1306};
-use hal::fugit::RateExtU32;
-use pio_proc::pio_asm;
+use twelve_projects_of_codemas::color::PowerLimit;
+use twelve_projects_of_codemas::effects::Effect;
+use twelve_projects_of_codemas::neopixel::NeoPixelStrip;
+use twelve_projects_of_codemas::strip::Strip;
+use twelve_projects_of_codemas::ws2812::Chipset;
+
+const WHITE: RGB8 = RGB8::new(0xFF, 0xFF, 0xFF);
+const PURPLE: RGB8 = RGB8::new(0xFF, 0x00, 0xFF);
+const GREEN: RGB8 = RGB8::new(0x00, 0xFF, 0x00);
+const BLUE: RGB8 = RGB8::new(0x00, 0x00, 0xFF);
+const YELLOW: RGB8 = RGB8::new(0xFF, 0xFF, 0x00);
+const CYAN: RGB8 = RGB8::new(0x00, 0xFF, 0xFF);
+const RED: RGB8 = RGB8::new(0xFF, 0x00, 0x00);
+const ORANGE: RGB8 = RGB8::new(0xFF, 0xA5, 0x00);
+const PINK: RGB8 = RGB8::new(0xFF, 0x14, 0x93);
+
+const COLORS: [RGB8; 15] = [
+    WHITE, PURPLE, GREEN, BLUE, YELLOW, CYAN, WHITE, RED, ORANGE, WHITE, WHITE, WHITE, WHITE, WHITE, WHITE,
+];
+
+// Change `PATTERN` to pick which one is shown, the others are never built.
+#[allow(dead_code)]
+enum Pattern {
+    Colors,
+    RedBlue,
+    Pink,
+    Animated(Effect),
+}
+
+const PATTERN: Pattern = Pattern::Pink;
 
 #[entry]
 fn main() -> ! {
@@ -49,6 +62,7 @@ fn main() -> ! {
     .unwrap();
 
     let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
 
     let sio = hal::Sio::new(pac.SIO);
 
@@ -59,142 +73,49 @@ fn main() -> ! {
         &mut pac.RESETS,
     );
 
-    let led_pin = pins.gpio18.into_push_pull_output();
     let neopixel_pin: Pin<_, FunctionPio0, _> = pins.gpio28.into_function();
-    let neopixel_pin_id = neopixel_pin.id().num;
-
-    let program = pio_proc::pio_asm!(
-        ".side_set 1",
-        ".wrap_target",
-        "bitloop:",
-            "out x 1 side 0 [2]",
-            "jmp !x do_zero side 1 [1]",
-        "do_one:",
-            "jmp bitloop side 1 [4]",
-        "do_zero:",
-            "nop side 0 [4]",
-        ".wrap",
-        options(max_program_size = 32)
-    );
 
     let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
-    let installed = pio.install(&program.program).unwrap();
-    let (mut sm, _, mut tx) = hal::pio::PIOBuilder::from_program(installed)
-        .out_shift_direction(hal::pio::ShiftDirection::Left)
-        .pull_threshold(32)
-        .autopull(true)
-        .side_set_pin_base(neopixel_pin_id)
-        .clock_divisor_fixed_point(15, 160)
-        .build(sm0);
-    sm.set_pindirs([(neopixel_pin_id, hal::pio::PinDir::Output)]);
-    sm.start();
-
-    // grb
-    let colors = &[
-        0xFF, 0xFF, 0xFF, // white
-        0x00, 0xFF, 0xFF, // purple
-        0xFF, 0x00, 0x00, // green
-        0x00, 0x00, 0xFF, // blue
-        0xFF, 0xFF, 0x00, // yellow
-        0xFF, 0x00, 0xFF, // cyan
-        0xFF, 0xFF, 0xFF, // white
-        0x00, 0xFF, 0x00, // red
-        0xa5, 0xFF, 0x00, // orange
-        0xFF, 0xFF, 0xFF,
-        0xFF, 0xFF, 0xFF,
-        0xFF, 0xFF, 0xFF,
-        0xFF, 0xFF, 0xFF,
-        0xFF, 0xFF, 0xFF,
-        0xFF, 0xFF, 0xFF,
-        0x00, 0x00, 0x00, // Placeholders so that length % 4 == 0
-    ];
-
-    let red = &[
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0xFF, 0x00, 
-        0x00, 0x00, 0x00, // Placeholders so that length % 4 == 0
-    ];
-
-    let blue = &[
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0xFF,
-        0x00, 0x00, 0x00// Placeholders so that length % 4 == 0
-    ];
-
-    let pink = &[
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x14, 0xFF, 0x93,
-        0x00, 0x00, 0x00// Placeholders so that length % 4 == 0
-    ];
+    let dma = pac.DMA.split(&mut pac.RESETS);
+    let buffers = cortex_m::singleton!(: [[u32; 15]; 2] = [[0; 15]; 2]).unwrap();
+    let mut strip: NeoPixelStrip<_, _, _, 15> = NeoPixelStrip::new(
+        neopixel_pin,
+        &mut pio,
+        sm0,
+        clocks.system_clock.freq(),
+        timer,
+        Chipset::Ws2812b,
+        dma.ch0,
+        buffers,
+    );
+    // 15 pixels at full white would draw about 900 mA, more than USB gives.
+    strip.set_power_limit(Some(PowerLimit::ws2812b(500)));
 
     loop {
-
-        // -------------- Show colors in array --------------
-        // for val in colors.chunks_exact(4).map(|v| (v[0] << 24) + (v[1] << 16) + (v[2] << 8) + v[3]) {
-        //     tx.write(val);
-        //     delay.delay_us(50);
-        // }
-
-        // -------------- Alternate red / blue --------------
-        // for val in red.chunks_exact(4).map(|v| (v[0] << 24) + (v[1] << 16) + (v[2] << 8) + v[3]) {
-        //     tx.write(val);
-        //     delay.delay_us(50);
-        // }
-
-        // delay.delay_ms(500);
-
-        // for val in blue.chunks_exact(4).map(|v| (v[0] << 24) + (v[1] << 16) + (v[2] << 8) + v[3]) {
-        //     tx.write(val);
-        //     delay.delay_us(50);
-        // }
-
-        //delay.delay_ms(500);
-
-        // -------------- Pink --------------
-        for val in pink.chunks_exact(4).map(|v| (v[0] << 24) + (v[1] << 16) + (v[2] << 8) + v[3]) {
-            tx.write(val);
-            delay.delay_us(50);
+        match PATTERN {
+            Pattern::Colors => {
+                *strip.pixels_mut() = COLORS;
+                strip.write().unwrap();
+                delay.delay_ms(10);
+            }
+            Pattern::RedBlue => {
+                for color in [RED, BLUE] {
+                    strip.fill(color);
+                    strip.write().unwrap();
+                    delay.delay_ms(500);
+                }
+            }
+            Pattern::Pink => {
+                strip.fill(PINK);
+                strip.write().unwrap();
+                delay.delay_ms(10);
+            }
+            Pattern::Animated(effect) => {
+                let now_ms = (timer.get_counter().ticks() / 1000) as u32;
+                effect.render(now_ms, strip.pixels_mut());
+                strip.write().unwrap();
+                delay.delay_ms(10);
+            }
         }
-        delay.delay_ms(10);
-
     }
 }
@@ -1,18 +1,33 @@
+/*
+Three buttons lighting three LEDs while they're held.
+
+  buttons          gpio13, gpio12, gpio11, to 3V3
+  LEDs             gpio18, gpio19, gpio20
+
+The buttons are read by edge interrupt, see src/input.rs, and the core
+sleeps in between.
+*/
+
 #![no_std]
 #![no_main]
 
-use embedded_hal::digital::v2::InputPin;
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
 use embedded_hal::digital::v2::OutputPin;
 use panic_halt as _;
 use rp_pico::entry;
 use rp_pico::hal;
 use rp_pico::hal::pac;
-use rp_pico::hal::prelude::*;
+use rp_pico::hal::pac::interrupt;
+use twelve_projects_of_codemas::input::{self, EdgeInputs, EventQueue};
+
+const QUEUE: usize = 16;
+
+static INPUTS: Mutex<RefCell<Option<EdgeInputs<'static, 3, QUEUE>>>> = Mutex::new(RefCell::new(None));
 
 #[entry]
 fn main() -> ! {
     let mut pac = pac::Peripherals::take().unwrap();
-    let core = pac::CorePeripherals::take().unwrap();
 
     let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
 
@@ -28,7 +43,7 @@ fn main() -> ! {
     .ok()
     .unwrap();
 
-    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
 
     let sio = hal::Sio::new(pac.SIO);
 
@@ -43,25 +58,38 @@ fn main() -> ! {
     let mut led_pin2 = pins.gpio19.into_push_pull_output();
     let mut led_pin3 = pins.gpio20.into_push_pull_output();
 
-    let button1 = pins.gpio13.into_pull_down_input();
-    let button2 = pins.gpio12.into_pull_down_input();
-    let button3 = pins.gpio11.into_pull_down_input();
+    let queue = cortex_m::singleton!(: EventQueue<QUEUE> = EventQueue::new()).unwrap();
+    let (sender, mut receiver) = input::split(queue);
+    let mut inputs = EdgeInputs::new(timer, sender);
+    // Numbered 0, 1 and 2, the same as the LEDs below.
+    inputs.add(pins.gpio13.into_pull_down_input());
+    inputs.add(pins.gpio12.into_pull_down_input());
+    inputs.add(pins.gpio11.into_pull_down_input());
 
-    loop {
-        if button1.is_high().unwrap() {
-            led_pin.set_high().unwrap()
-        }
+    cortex_m::interrupt::free(|cs| {
+        INPUTS.borrow(cs).replace(Some(inputs));
+    });
 
-        if button2.is_high().unwrap() {
-            led_pin2.set_high().unwrap();
-        }
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0);
+    }
 
-        if button3.is_high().unwrap() {
-            led_pin3.set_high().unwrap();
+    loop {
+        let event = receiver.wait();
+        let state = event.edge.is_high().into();
+        match event.input {
+            0 => led_pin.set_state(state).unwrap(),
+            1 => led_pin2.set_state(state).unwrap(),
+            _ => led_pin3.set_state(state).unwrap(),
         }
-
-        led_pin.set_low().unwrap();
-        led_pin2.set_low().unwrap();
-        led_pin3.set_low().unwrap();
     }
 }
+
+#[interrupt]
+fn IO_IRQ_BANK0() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(inputs) = INPUTS.borrow(cs).borrow_mut().as_mut() {
+            inputs.service();
+        }
+    });
+}
@@ -2,7 +2,6 @@
 #![no_main]
 
 use cortex_m::delay::Delay;
-use defmt_serial as _;
 use embedded_hal::adc::OneShot;
 use embedded_hal::can::nb;
 use embedded_hal::digital::v2::InputPin;
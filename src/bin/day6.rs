@@ -1,7 +1,6 @@
 #![no_std]
 #![no_main]
 
-use defmt_serial as _;
 use embedded_hal::adc::OneShot;
 use embedded_hal::digital::v2::InputPin;
 use embedded_hal::digital::v2::OutputPin;
@@ -1,17 +1,30 @@
+/*
+The PIR sensor lighting an LED while it sees movement.
+
+  PIR output       gpio16
+  LED              gpio18
+
+The sensor is read by edge interrupt, see src/input.rs, and the core sleeps
+in between.
+*/
+
 #![no_std]
 #![no_main]
 
-use defmt_serial as _;
-use embedded_hal::adc::OneShot;
-use embedded_hal::digital::v2::InputPin;
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
 use embedded_hal::digital::v2::OutputPin;
 use panic_halt as _;
 use rp_pico::entry;
 use rp_pico::hal;
-use rp_pico::hal::adc::AdcPin;
 use rp_pico::hal::pac;
+use rp_pico::hal::pac::interrupt;
 use rp_pico::hal::prelude::*;
-use rp_pico::hal::Adc;
+use twelve_projects_of_codemas::input::{self, EdgeInputs, EventQueue};
+
+const QUEUE: usize = 8;
+
+static INPUTS: Mutex<RefCell<Option<EdgeInputs<'static, 1, QUEUE>>>> = Mutex::new(RefCell::new(None));
 
 #[entry]
 fn main() -> ! {
@@ -33,6 +46,7 @@ fn main() -> ! {
     .unwrap();
 
     let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
 
     let sio = hal::Sio::new(pac.SIO);
 
@@ -49,15 +63,32 @@ fn main() -> ! {
     // wait for sensor to warm up
     delay.delay_ms(10000);
 
-    loop {
-        
-        if pir_pin.is_high().unwrap() {
-            led_pin.set_high().unwrap();
-        }
-        else {
-            led_pin.set_low().unwrap();
-        }
+    let queue = cortex_m::singleton!(: EventQueue<QUEUE> = EventQueue::new()).unwrap();
+    let (sender, mut receiver) = input::split(queue);
+    let mut inputs = EdgeInputs::new(timer, sender);
+    let pir = inputs.add(pir_pin);
+    // It may already be seeing something.
+    led_pin.set_state(inputs.is_high(pir).unwrap().into()).unwrap();
 
-        delay.delay_ms(100);
+    cortex_m::interrupt::free(|cs| {
+        INPUTS.borrow(cs).replace(Some(inputs));
+    });
+
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0);
+    }
+
+    loop {
+        let event = receiver.wait();
+        led_pin.set_state(event.edge.is_high().into()).unwrap();
     }
 }
+
+#[interrupt]
+fn IO_IRQ_BANK0() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(inputs) = INPUTS.borrow(cs).borrow_mut().as_mut() {
+            inputs.service();
+        }
+    });
+}
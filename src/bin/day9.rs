@@ -1,22 +1,46 @@
+/*
+The tilt switch as an input, see src/tilt.rs.
+
+  tilt switch      gpio26, to 3V3, closed when tilted
+  tilt LED         gpio18, lit while tilted
+  shake LED        gpio19, toggles on every shake
+  hold LED         gpio20, lit once it's been tilted for the hold time
+
+Shaking turns the hold LED off as well, until the next time it's held
+tilted.
+
+The switch is read by edge interrupt, see src/input.rs. In between the
+core sleeps, with a timer alarm to wake it when the debounce or hold time
+runs out.
+*/
+
 #![no_std]
 #![no_main]
 
-use defmt_serial as _;
-use embedded_hal::adc::OneShot;
-use embedded_hal::digital::v2::InputPin;
-use embedded_hal::digital::v2::OutputPin;
+use core::cell::RefCell;
+use cortex_m::interrupt::Mutex;
+use embedded_hal::digital::v2::{InputPin, OutputPin, ToggleableOutputPin};
 use panic_halt as _;
 use rp_pico::entry;
 use rp_pico::hal;
-use rp_pico::hal::adc::AdcPin;
 use rp_pico::hal::pac;
-use rp_pico::hal::prelude::*;
-use rp_pico::hal::Adc;
+use rp_pico::hal::pac::interrupt;
+use rp_pico::hal::timer::{Alarm, Alarm0, Instant};
+use twelve_projects_of_codemas::input::{self, EdgeInputs, EventQueue};
+use twelve_projects_of_codemas::tilt::{Orientation, TiltConfig, TiltEvent, TiltSensor};
+
+const QUEUE: usize = 32;
+
+static INPUTS: Mutex<RefCell<Option<EdgeInputs<'static, 1, QUEUE>>>> = Mutex::new(RefCell::new(None));
+static ALARM: Mutex<RefCell<Option<Alarm0>>> = Mutex::new(RefCell::new(None));
+
+fn ms(us: u64) -> u32 {
+    (us / 1000) as u32
+}
 
 #[entry]
 fn main() -> ! {
     let mut pac = pac::Peripherals::take().unwrap();
-    let core = pac::CorePeripherals::take().unwrap();
 
     let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
 
@@ -32,7 +56,7 @@ fn main() -> ! {
     .ok()
     .unwrap();
 
-    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+    let mut timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
 
     let sio = hal::Sio::new(pac.SIO);
 
@@ -44,17 +68,95 @@ fn main() -> ! {
     );
 
     let tilt_pin = pins.gpio26.into_pull_down_input();
-    let mut led_pin = pins.gpio18.into_push_pull_output();
+    let mut tilt_led = pins.gpio18.into_push_pull_output();
+    let mut shake_led = pins.gpio19.into_push_pull_output();
+    let mut hold_led = pins.gpio20.into_push_pull_output();
+
+    // After this it's only known from the edges.
+    let mut closed = tilt_pin.is_high().unwrap();
+
+    let queue = cortex_m::singleton!(: EventQueue<QUEUE> = EventQueue::new()).unwrap();
+    let (sender, mut receiver) = input::split(queue);
+    let mut inputs = EdgeInputs::new(timer, sender);
+    inputs.add(tilt_pin);
+
+    let mut alarm = timer.alarm_0().unwrap();
+    alarm.enable_interrupt();
+
+    cortex_m::interrupt::free(|cs| {
+        INPUTS.borrow(cs).replace(Some(inputs));
+        ALARM.borrow(cs).replace(Some(alarm));
+    });
+
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0);
+        pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0);
+    }
+
+    let mut sensor = TiltSensor::new(TiltConfig::default());
 
     loop {
-        
-        if tilt_pin.is_high().unwrap() {
-            led_pin.set_high().unwrap();
+        // Edges that have queued up first, in order, then the time.
+        let tilt_event = match receiver.next_event() {
+            Some(event) => {
+                closed = event.edge.is_high();
+                sensor.update(closed, ms(event.at_us))
+            }
+            None => {
+                let now_us = timer.get_counter().ticks();
+                sensor.update(closed, ms(now_us)).or_else(|| {
+                    // Nothing to do until the next edge or the sensor's
+                    // deadline.
+                    let deadline_us = sensor
+                        .deadline_ms()
+                        .map(|deadline| now_us + deadline.wrapping_sub(ms(now_us)) as u64 * 1000);
+                    if let Some(deadline_us) = deadline_us {
+                        cortex_m::interrupt::free(|cs| {
+                            if let Some(alarm) = ALARM.borrow(cs).borrow_mut().as_mut() {
+                                alarm.schedule_at(Instant::from_ticks(deadline_us)).ok();
+                            }
+                        });
+                    }
+                    let timed_out = || deadline_us.is_some_and(|deadline| timer.get_counter().ticks() >= deadline);
+                    let event = receiver.wait_until(timed_out)?;
+                    closed = event.edge.is_high();
+                    sensor.update(closed, ms(event.at_us))
+                })
+            }
+        };
+
+        match tilt_event {
+            Some(TiltEvent::Changed(orientation)) => {
+                tilt_led.set_state((orientation == Orientation::Tilted).into()).unwrap();
+                hold_led.set_low().unwrap();
+            }
+            Some(TiltEvent::Held(Orientation::Tilted)) => hold_led.set_high().unwrap(),
+            Some(TiltEvent::Shake) => {
+                // There's no `Changed` for the last flip of a shake.
+                tilt_led.set_state((sensor.orientation() == Orientation::Tilted).into()).unwrap();
+                shake_led.toggle().unwrap();
+                hold_led.set_low().unwrap();
+            }
+            _ => {}
         }
-        else {
-            led_pin.set_low().unwrap();
+    }
+}
+
+#[interrupt]
+fn IO_IRQ_BANK0() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(inputs) = INPUTS.borrow(cs).borrow_mut().as_mut() {
+            inputs.service();
         }
+    });
+}
 
-        delay.delay_ms(100);
-    }
+// Only here to wake the main loop.
+#[interrupt]
+fn TIMER_IRQ_0() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(alarm) = ALARM.borrow(cs).borrow_mut().as_mut() {
+            alarm.clear_interrupt();
+        }
+    });
 }
@@ -0,0 +1,118 @@
+#![no_std]
+#![no_main]
+
+use embedded_hal::adc::OneShot;
+use embedded_hal::digital::v2::OutputPin;
+use panic_halt as _;
+use rp_pico::entry;
+use rp_pico::hal;
+use rp_pico::hal::adc::AdcPin;
+use rp_pico::hal::pac;
+use rp_pico::hal::Adc;
+use twelve_projects_of_codemas::protocol::{Decoder, Message, NackReason, Packet, Sensor, MAX_FRAME};
+use usb_device::{class_prelude::*, prelude::*};
+use usbd_serial::SerialPort;
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    let clocks = hal::clocks::init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+
+    let sio = hal::Sio::new(pac.SIO);
+
+    let pins = rp_pico::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+
+    let mut serial = SerialPort::new(&usb_bus);
+
+    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .manufacturer("Trevor")
+        .product("Serial port")
+        .serial_number("0")
+        .device_class(2)
+        .build();
+
+    let mut adc = Adc::new(pac.ADC, &mut pac.RESETS);
+    let mut pot_pin = AdcPin::new(pins.gpio27.into_floating_input());
+
+    let mut led_red = pins.gpio18.into_push_pull_output();
+    let mut led_yellow = pins.gpio19.into_push_pull_output();
+    let mut led_green = pins.gpio20.into_push_pull_output();
+
+    let mut decoder = Decoder::new();
+    let mut seq: u8 = 0;
+    let mut last_reading = timer.get_counter();
+
+    let mut send = |serial: &mut SerialPort<_>, message: Message| {
+        let mut frame = [0u8; MAX_FRAME];
+        if let Ok(n) = Packet::new(seq, message).encode(&mut frame) {
+            let _ = serial.write(&frame[..n]);
+        }
+        seq = seq.wrapping_add(1);
+    };
+
+    loop {
+        if usb_dev.poll(&mut [&mut serial]) {
+            let mut buf = [0u8; 64];
+            if let Ok(count) = serial.read(&mut buf) {
+                for &byte in &buf[..count] {
+                    let packet = match decoder.push(byte) {
+                        Some(Ok(packet)) => packet,
+                        _ => continue,
+                    };
+
+                    let reply = match packet.message {
+                        Message::Led { index, on } => {
+                            let pin_result = match index {
+                                0 => Some(led_red.set_state(on.into())),
+                                1 => Some(led_yellow.set_state(on.into())),
+                                2 => Some(led_green.set_state(on.into())),
+                                _ => None,
+                            };
+                            match pin_result {
+                                Some(_) => Message::Ack { seq: packet.seq },
+                                None => Message::Nack { seq: packet.seq, reason: NackReason::BadArgument },
+                            }
+                        }
+                        _ => Message::Nack { seq: packet.seq, reason: NackReason::Unsupported },
+                    };
+                    send(&mut serial, reply);
+                }
+            }
+        }
+
+        let now = timer.get_counter();
+        if (now - last_reading).to_millis() >= 500 {
+            last_reading = now;
+            let value: u16 = adc.read(&mut pot_pin).unwrap_or(0);
+            send(&mut serial, Message::SensorReading { sensor: Sensor::Potentiometer, value: value as i32 });
+        }
+    }
+}
@@ -0,0 +1,157 @@
+#![no_std]
+#![no_main]
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use panic_halt as _;
+use rp_pico::Gp0I2C0Sda;
+use rp_pico::Gp1I2C0Scl;
+use rp_pico::entry;
+use rp_pico::hal;
+use rp_pico::hal::I2C;
+use rp_pico::hal::pac;
+use rp_pico::hal::prelude::*;
+use twelve_projects_of_codemas::display::Screen;
+use twelve_projects_of_codemas::menu::{self, Button, Event, Item, ItemKind, Menu, Navigator};
+use twelve_projects_of_codemas::oled::DisplayConfig;
+
+// Slots in `values`.
+const LIGHT_THRESHOLD: usize = 0;
+const TEMP_THRESHOLD: usize = 1;
+const TEMP_UNITS: usize = 2;
+const MOTION_ALARM: usize = 3;
+const LED1: usize = 4;
+const LED2: usize = 5;
+const LED3: usize = 6;
+
+const DEFAULTS: [i32; 7] = [50, 25, 0, 1, 0, 0, 0];
+
+const RESET: u8 = 0;
+
+static THRESHOLDS: Menu = Menu {
+    title: "Thresholds",
+    items: &[
+        Item { label: "Light %", kind: ItemKind::Number { slot: LIGHT_THRESHOLD, min: 0, max: 100, step: 5 } },
+        Item { label: "Temp", kind: ItemKind::Number { slot: TEMP_THRESHOLD, min: -10, max: 50, step: 1 } },
+        Item { label: "Units", kind: ItemKind::Choice { slot: TEMP_UNITS, options: &["C", "F"] } },
+        Item { label: "Motion alarm", kind: ItemKind::Toggle { slot: MOTION_ALARM } },
+    ],
+};
+
+static LEDS: Menu = Menu {
+    title: "LEDs",
+    items: &[
+        Item { label: "LED 1", kind: ItemKind::Toggle { slot: LED1 } },
+        Item { label: "LED 2", kind: ItemKind::Toggle { slot: LED2 } },
+        Item { label: "LED 3", kind: ItemKind::Toggle { slot: LED3 } },
+    ],
+};
+
+static ROOT: Menu = Menu {
+    title: "Settings",
+    items: &[
+        Item { label: "Thresholds", kind: ItemKind::Submenu(&THRESHOLDS) },
+        Item { label: "LEDs", kind: ItemKind::Submenu(&LEDS) },
+        Item { label: "Reset", kind: ItemKind::Action(RESET) },
+    ],
+};
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let core = pac::CorePeripherals::take().unwrap();
+
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    let clocks = hal::clocks::init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+
+    let sio = hal::Sio::new(pac.SIO);
+
+    let pins = rp_pico::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let sda_pin: Gp0I2C0Sda = pins.gpio0.reconfigure();
+    let scl_pin: Gp1I2C0Scl = pins.gpio1.reconfigure();
+
+    let config = DisplayConfig::default();
+
+    let i2c = I2C::i2c0(
+        pac.I2C0,
+        sda_pin,
+        scl_pin,
+        config.bus_speed,
+        &mut pac.RESETS,
+        clocks.system_clock.freq(),
+    );
+
+    let mut led_pin = pins.gpio18.into_push_pull_output();
+    let mut led_pin2 = pins.gpio19.into_push_pull_output();
+    let mut led_pin3 = pins.gpio20.into_push_pull_output();
+
+    // Top to bottom as wired in day3.
+    let up = pins.gpio13.into_pull_down_input();
+    let select = pins.gpio12.into_pull_down_input();
+    let down = pins.gpio11.into_pull_down_input();
+
+    let display = match config.build(i2c) {
+        Ok(display) => display,
+        Err(_) => loop {
+            led_pin.set_high().unwrap();
+            delay.delay_ms(100);
+            led_pin.set_low().unwrap();
+            delay.delay_ms(100);
+        },
+    };
+
+    let mut screen = Screen::new(display);
+    let mut values = DEFAULTS;
+    let mut nav = Navigator::new(&ROOT, menu::visible_rows(screen.size().height));
+
+    let mut was_pressed = [false; 3];
+    let mut redraw = true;
+
+    loop {
+        if redraw {
+            let status = if values != DEFAULTS { "*" } else { "" };
+            nav.draw(&mut screen, &values, status).unwrap();
+            screen.flush().unwrap();
+            redraw = false;
+        }
+
+        let pressed = [up.is_high().unwrap(), select.is_high().unwrap(), down.is_high().unwrap()];
+        for (i, button) in [Button::Up, Button::Select, Button::Down].into_iter().enumerate() {
+            if !pressed[i] || was_pressed[i] {
+                continue;
+            }
+            // Changes land in `values` directly, only the reset needs
+            // handling here.
+            if let Some(Event::Action(RESET)) = nav.press(button, &mut values) {
+                values = DEFAULTS;
+            }
+            redraw = true;
+        }
+        was_pressed = pressed;
+
+        led_pin.set_state((values[LED1] != 0).into()).unwrap();
+        led_pin2.set_state((values[LED2] != 0).into()).unwrap();
+        led_pin3.set_state((values[LED3] != 0).into()).unwrap();
+
+        // Also long enough for the buttons to stop bouncing.
+        delay.delay_ms(10);
+    }
+}
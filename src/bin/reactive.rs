@@ -0,0 +1,138 @@
+/*
+The day12 strip following the sensors, see src/reactive.rs.
+
+  light sensor     gpio26 (ADC0)    as day6
+  potentiometer    gpio27 (ADC1)    as day4
+  DS18B20          gpio22           as the dashboard
+  NeoPixels        gpio28           as day12
+
+Like day8, needs to be run in release mode otherwise the DS18B20 gives
+OneWireError::UnexpectedResponse.
+*/
+
+#![no_std]
+#![no_main]
+
+use embedded_hal::adc::OneShot;
+use one_wire_bus::OneWire;
+use panic_halt as _;
+use rp_pico::entry;
+use rp_pico::hal;
+use rp_pico::hal::adc::{Adc, AdcPin};
+use rp_pico::hal::dma::DMAExt;
+use rp_pico::hal::gpio::FunctionPio0;
+use rp_pico::hal::gpio::Pin;
+use rp_pico::hal::pac;
+use rp_pico::hal::pio::PIOExt;
+use rp_pico::hal::prelude::*;
+use twelve_projects_of_codemas::color::PowerLimit;
+use twelve_projects_of_codemas::dashboard::{adc_percent, Readings};
+use twelve_projects_of_codemas::effects::{Animator, Effect};
+use twelve_projects_of_codemas::neopixel::NeoPixelStrip;
+use twelve_projects_of_codemas::reactive::{Binding, Curve, Param, Reactive, Source};
+use twelve_projects_of_codemas::strip::Strip;
+use twelve_projects_of_codemas::thermometer::Thermometer;
+use twelve_projects_of_codemas::ws2812::Chipset;
+
+const LEDS: usize = 15;
+
+// Turn the knob to change colour, and the strip dims as the room gets dark.
+const KNOB_AND_LIGHT: &[Binding] = &[
+    Binding { source: Source::Potentiometer, param: Param::Hue, curve: Curve(&[(0, 0), (100, 255)]) },
+    Binding { source: Source::Light, param: Param::Brightness, curve: Curve(&[(0, 20), (60, 255)]) },
+];
+
+// Blue below 16 C through green to red above 28 C, the knob sets the speed.
+#[allow(dead_code)]
+const TEMPERATURE: &[Binding] = &[
+    Binding { source: Source::Temperature, param: Param::Hue, curve: Curve(&[(160, 170), (220, 85), (280, 0)]) },
+    Binding { source: Source::Potentiometer, param: Param::Speed, curve: Curve(&[(0, 20), (100, 255)]) },
+];
+
+// Change `BINDINGS` to pick which set is used.
+const BINDINGS: &[Binding] = KNOB_AND_LIGHT;
+
+const TICK_MS: u32 = 20;
+// A temperature reading is started at the top of each period and read back
+// halfway through, as in the dashboard.
+const TEMPERATURE_PERIOD: u32 = 100;
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let core = pac::CorePeripherals::take().unwrap();
+
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    let clocks = hal::clocks::init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+
+    let sio = hal::Sio::new(pac.SIO);
+
+    let pins = rp_pico::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut adc = Adc::new(pac.ADC, &mut pac.RESETS);
+    let mut light_pin = AdcPin::new(pins.gpio26.into_floating_input());
+    let mut pot_pin = AdcPin::new(pins.gpio27.into_floating_input());
+
+    let mut one_wire_bus = OneWire::new(hal::gpio::InOutPin::new(pins.gpio22)).unwrap();
+
+    let neopixel_pin: Pin<_, FunctionPio0, _> = pins.gpio28.into_function();
+    let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+    let dma = pac.DMA.split(&mut pac.RESETS);
+    let buffers = cortex_m::singleton!(: [[u32; LEDS]; 2] = [[0; LEDS]; 2]).unwrap();
+    let mut strip: NeoPixelStrip<_, _, _, LEDS> = NeoPixelStrip::new(
+        neopixel_pin,
+        &mut pio,
+        sm0,
+        clocks.system_clock.freq(),
+        timer,
+        Chipset::Ws2812b,
+        dma.ch0,
+        buffers,
+    );
+    strip.set_power_limit(Some(PowerLimit::ws2812b(500)));
+
+    let mut animator = Animator::new(Effect::Breathing((255, 255, 255).into()));
+    let mut reactive = Reactive::new(BINDINGS);
+    let mut thermometer = Thermometer::new();
+    let mut temperature = None;
+    let mut tick: u32 = 0;
+
+    loop {
+        if tick == 0 {
+            thermometer.start(&mut one_wire_bus, &mut delay);
+        } else if tick == TEMPERATURE_PERIOD / 2 {
+            temperature = thermometer.read(&mut one_wire_bus, &mut delay);
+        }
+        tick = (tick + 1) % TEMPERATURE_PERIOD;
+
+        let light: u16 = adc.read(&mut light_pin).unwrap_or(0);
+        let pot: u16 = adc.read(&mut pot_pin).unwrap_or(0);
+        let readings = Readings { light: adc_percent(light), potentiometer: adc_percent(pot), ..Default::default() };
+        reactive.update(&readings, temperature, &mut animator);
+
+        let now_ms = (timer.get_counter().ticks() / 1000) as u32;
+        animator.render(now_ms, strip.pixels_mut());
+        strip.write().unwrap();
+
+        delay.delay_ms(TICK_MS);
+    }
+}
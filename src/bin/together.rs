@@ -0,0 +1,197 @@
+/*
+Several projects at once on one board, see src/scheduler.rs.
+
+  LEDs             gpio18, gpio19, gpio20    day2's sequence
+  DS18B20          gpio26                    as day8
+  USB serial                                 the temperature, as usb.rs
+
+Like day8, needs to be run in release mode otherwise the DS18B20 gives
+OneWireError::UnexpectedResponse.
+*/
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+use core::fmt::Write;
+
+use cortex_m::interrupt::Mutex;
+use embedded_hal::digital::v2::OutputPin;
+use one_wire_bus::OneWire;
+use panic_halt as _;
+use rp_pico::entry;
+use rp_pico::hal;
+use rp_pico::hal::pac;
+use rp_pico::hal::pac::interrupt;
+use rp_pico::hal::prelude::*;
+use rp_pico::hal::timer::{Alarm, Alarm0, Instant};
+use twelve_projects_of_codemas::scheduler::{Clock, Scheduler, TaskId};
+use twelve_projects_of_codemas::thermometer::Thermometer;
+use twelve_projects_of_codemas::usb_console::UsbConsole;
+use usb_device::{class_prelude::*, prelude::*};
+use usbd_serial::SerialPort;
+
+type Console = UsbConsole<'static, hal::usb::UsbBus, 256>;
+
+static USB_DEVICE: Mutex<RefCell<Option<UsbDevice<hal::usb::UsbBus>>>> = Mutex::new(RefCell::new(None));
+static CONSOLE: Mutex<RefCell<Option<Console>>> = Mutex::new(RefCell::new(None));
+static ALARM: Mutex<RefCell<Option<Alarm0>>> = Mutex::new(RefCell::new(None));
+
+const BLINK_US: u64 = 500_000;
+const TEMPERATURE_US: u64 = 2_000_000;
+// With some room to spare over thermometer::CONVERSION_MS.
+const CONVERSION_US: u64 = 800_000;
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let core = pac::CorePeripherals::take().unwrap();
+
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    let clocks = hal::clocks::init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+    let mut timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+
+    let sio = hal::Sio::new(pac.SIO);
+
+    let pins = rp_pico::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let mut leds = [
+        pins.gpio18.into_push_pull_output().into_dyn_pin(),
+        pins.gpio19.into_push_pull_output().into_dyn_pin(),
+        pins.gpio20.into_push_pull_output().into_dyn_pin(),
+    ];
+
+    let mut one_wire_bus = OneWire::new(hal::gpio::InOutPin::new(pins.gpio26)).unwrap();
+
+    let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    ));
+    let usb_bus: &'static _ = cortex_m::singleton!(: UsbBusAllocator<hal::usb::UsbBus> = usb_bus).unwrap();
+
+    let serial = SerialPort::new(usb_bus);
+    let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .manufacturer("Trevor")
+        .product("Serial port")
+        .serial_number("0")
+        .device_class(2)
+        .build();
+
+    let mut alarm = timer.alarm_0().unwrap();
+    alarm.enable_interrupt();
+
+    cortex_m::interrupt::free(|cs| {
+        USB_DEVICE.borrow(cs).replace(Some(usb_dev));
+        CONSOLE.borrow(cs).replace(Some(UsbConsole::new(serial)));
+        ALARM.borrow(cs).replace(Some(alarm));
+    });
+
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::USBCTRL_IRQ);
+        pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0);
+    }
+
+    let start = timer.now_us();
+    let mut scheduler: Scheduler<3> = Scheduler::new();
+    let blink = scheduler.every(BLINK_US, start);
+    let start_conversion = scheduler.every(TEMPERATURE_US, start);
+    let read_temperature = scheduler.every(TEMPERATURE_US, start + CONVERSION_US);
+
+    // Which step of day2's on, on, on, off, off, off the LEDs are at.
+    let mut step = 0;
+    let mut thermometer = Thermometer::new();
+
+    loop {
+        while let Some(task) = scheduler.poll(&mut timer) {
+            match task {
+                t if t == blink => {
+                    leds[step % 3].set_state((step < 3).into()).unwrap();
+                    step = (step + 1) % 6;
+                }
+                t if t == start_conversion => {
+                    thermometer.start(&mut one_wire_bus, &mut delay);
+                }
+                t if t == read_temperature => {
+                    let temperature = thermometer.read(&mut one_wire_bus, &mut delay);
+                    let late_ms = scheduler.stats(blink).map_or(0, |s| s.worst_late_us / 1000);
+                    print(|console| match temperature {
+                        Some(celsius) => writeln!(console, "{:.1} C (LEDs up to {} ms late)", celsius, late_ms),
+                        None => writeln!(console, "No DS18B20 on gpio26"),
+                    });
+                }
+                TaskId(_) => {}
+            }
+        }
+
+        // Sleep until the next task's due, with the alarm set to wake the
+        // core then. The USB interrupt carries on regardless, and is kicked
+        // here in case the host was idle when a line was queued.
+        pac::NVIC::pend(pac::Interrupt::USBCTRL_IRQ);
+        let deadline_us = scheduler.next_deadline();
+        cortex_m::interrupt::free(|cs| {
+            if let (Some(deadline_us), Some(alarm)) = (deadline_us, ALARM.borrow(cs).borrow_mut().as_mut()) {
+                alarm.schedule_at(Instant::from_ticks(deadline_us)).ok();
+            }
+            // With interrupts masked the alarm can't go off between looking
+            // and sleeping, and a pending one still ends the wfi.
+            if deadline_us.is_none_or(|deadline_us| timer.now_us() < deadline_us) {
+                cortex_m::asm::wfi();
+            }
+        });
+    }
+}
+
+fn print(line: impl FnOnce(&mut Console) -> core::fmt::Result) {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(console) = CONSOLE.borrow(cs).borrow_mut().as_mut() {
+            let _ = line(console);
+        }
+    });
+}
+
+#[interrupt]
+fn USBCTRL_IRQ() {
+    cortex_m::interrupt::free(|cs| {
+        let mut usb_dev = USB_DEVICE.borrow(cs).borrow_mut();
+        let mut console = CONSOLE.borrow(cs).borrow_mut();
+        if let (Some(usb_dev), Some(console)) = (usb_dev.as_mut(), console.as_mut()) {
+            if console.poll(usb_dev) {
+                // Nothing is read back, discard input so the host doesn't
+                // stall waiting on us.
+                let mut buf = [0u8; 64];
+                console.read(&mut buf);
+            }
+        }
+    });
+}
+
+// Only here to wake the main loop.
+#[interrupt]
+fn TIMER_IRQ_0() {
+    cortex_m::interrupt::free(|cs| {
+        if let Some(alarm) = ALARM.borrow(cs).borrow_mut().as_mut() {
+            alarm.clear_interrupt();
+        }
+    });
+}
@@ -1,13 +1,37 @@
 #![no_std]
 #![no_main]
 
+use core::cell::RefCell;
+use core::fmt::Write;
+use cortex_m::interrupt::Mutex;
+use embedded_hal::digital::v2::InputPin;
 use rp_pico::{entry, hal::Clock};
 use panic_halt as _;
 use rp_pico::hal::pac;
+use rp_pico::hal::pac::interrupt;
 use rp_pico::hal;
+use twelve_projects_of_codemas::defmt_usb;
+use twelve_projects_of_codemas::keyboard::{self, key, modifier, Macro, MacroReports};
+use twelve_projects_of_codemas::usb_console::UsbConsole;
 use usb_device::{class_prelude::*, prelude::*};
+use usbd_hid::hid_class::HIDClass;
 use usbd_serial::SerialPort;
 
+type Console = UsbConsole<'static, hal::usb::UsbBus, 256>;
+
+static USB_DEVICE: Mutex<RefCell<Option<UsbDevice<hal::usb::UsbBus>>>> = Mutex::new(RefCell::new(None));
+static CONSOLE: Mutex<RefCell<Option<Console>>> = Mutex::new(RefCell::new(None));
+// Second CDC interface carrying defmt frames.
+static LOG_PORT: Mutex<RefCell<Option<SerialPort<hal::usb::UsbBus>>>> = Mutex::new(RefCell::new(None));
+static KEYBOARD: Mutex<RefCell<Option<HIDClass<hal::usb::UsbBus>>>> = Mutex::new(RefCell::new(None));
+
+// What each of the day3 buttons types when pressed.
+const MACROS: [Macro; 3] = [
+    Macro::Text("Hello from the Pico!\n"),
+    Macro::Hotkey { modifier: modifier::LEFT_CTRL | modifier::LEFT_ALT, key: key::T },
+    Macro::Hotkey { modifier: modifier::LEFT_GUI, key: key::L },
+];
+
 #[entry]
 fn main() -> ! {
     let core = pac::CorePeripherals::take().unwrap();
@@ -27,16 +51,17 @@ fn main() -> ! {
     .ok()
     .unwrap();
 
-    #[cfg(feature = "rp2040-e5")]
-    {
-        let sio = hal::Sio::new(pac.SIO);
-        let _pins = rp_pico::Pins::new(
-            pac.IO_BANK0,
-            pac.PADS_BANK0,
-            sio.gpio_bank0,
-            &mut pac.RESETS,
-        );
-    }
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = rp_pico::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let button1 = pins.gpio13.into_pull_down_input();
+    let button2 = pins.gpio12.into_pull_down_input();
+    let button3 = pins.gpio11.into_pull_down_input();
 
     let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
 
@@ -47,22 +72,114 @@ fn main() -> ! {
         true,
         &mut pac.RESETS,
     ));
+    let usb_bus: &'static _ = cortex_m::singleton!(: UsbBusAllocator<hal::usb::UsbBus> = usb_bus).unwrap();
 
-    let mut serial = SerialPort::new(&usb_bus);
+    let serial = SerialPort::new(usb_bus);
+    let log_port = SerialPort::new(usb_bus);
+    let keyboard = HIDClass::new_ep_in(usb_bus, keyboard::REPORT_DESCRIPTOR, 10);
 
-    let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
+    let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
         .manufacturer("Trevor")
         .product("Serial port")
         .serial_number("0")
-        .device_class(2)
+        .device_class(0xEF)
+        .device_sub_class(0x02)
+        .device_protocol(0x01)
+        .composite_with_iads()
         .build();
 
+    cortex_m::interrupt::free(|cs| {
+        USB_DEVICE.borrow(cs).replace(Some(usb_dev));
+        CONSOLE.borrow(cs).replace(Some(UsbConsole::new(serial)));
+        LOG_PORT.borrow(cs).replace(Some(log_port));
+        KEYBOARD.borrow(cs).replace(Some(keyboard));
+    });
+
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::USBCTRL_IRQ);
+    }
+
+    let mut count: u32 = 0;
+    let mut ticks: u32 = 0;
+    let mut was_pressed = [false; 3];
+    let mut playing: Option<MacroReports> = None;
+    let mut pending_report = None;
+
+    defmt::info!("usb example started");
+
     loop {
+        let pressed = [
+            button1.is_high().unwrap(),
+            button2.is_high().unwrap(),
+            button3.is_high().unwrap(),
+        ];
+        for (i, mac) in MACROS.iter().enumerate() {
+            if pressed[i] && !was_pressed[i] && playing.is_none() {
+                defmt::info!("button {=usize} pressed", i);
+                playing = Some(mac.reports());
+            }
+        }
+        was_pressed = pressed;
 
-        delay.delay_ms(100);
+        // One report per 10 ms, matching the HID poll interval. A report the
+        // host hasn't picked up yet is retried on the next pass.
+        if pending_report.is_none() {
+            pending_report = playing.as_mut().and_then(|reports| reports.next());
+            if pending_report.is_none() {
+                playing = None;
+            }
+        }
+        if let Some(report) = pending_report {
+            let sent = cortex_m::interrupt::free(|cs| {
+                KEYBOARD
+                    .borrow(cs)
+                    .borrow()
+                    .as_ref()
+                    .is_some_and(|hid| hid.push_raw_input(&report.to_bytes()).is_ok())
+            });
+            if sent {
+                pending_report = None;
+            }
+        }
 
-        if usb_dev.poll(&mut [&mut serial]) {
-            let _ = serial.write("Hello!\n".as_bytes());
+        if ticks == 0 {
+            defmt::debug!("tick {=u32}", count);
+
+            cortex_m::interrupt::free(|cs| {
+                if let Some(console) = CONSOLE.borrow(cs).borrow_mut().as_mut() {
+                    let _ = writeln!(console, "Hello! {} (dropped {})", count, console.dropped());
+                }
+            });
+            count = count.wrapping_add(1);
         }
+        // The interrupt only fires on bus activity, so kick the flush from
+        // here as well in case the host was idle when we queued the line.
+        pac::NVIC::pend(pac::Interrupt::USBCTRL_IRQ);
+
+        ticks = (ticks + 1) % 100;
+        delay.delay_ms(10);
     }
-}
\ No newline at end of file
+}
+
+#[interrupt]
+fn USBCTRL_IRQ() {
+    cortex_m::interrupt::free(|cs| {
+        let mut usb_dev = USB_DEVICE.borrow(cs).borrow_mut();
+        let mut console = CONSOLE.borrow(cs).borrow_mut();
+        let mut log_port = LOG_PORT.borrow(cs).borrow_mut();
+        let mut keyboard = KEYBOARD.borrow(cs).borrow_mut();
+        if let (Some(usb_dev), Some(console), Some(log_port), Some(keyboard)) =
+            (usb_dev.as_mut(), console.as_mut(), log_port.as_mut(), keyboard.as_mut())
+        {
+            if usb_dev.poll(&mut [console.serial(), log_port, keyboard]) {
+                // Nothing is read back yet, discard input so the host
+                // doesn't stall waiting on us.
+                let mut buf = [0u8; 64];
+                console.read(&mut buf);
+                let _ = log_port.read(&mut buf);
+            }
+            console.flush();
+            defmt_usb::drain(|bytes| log_port.write(bytes).unwrap_or(0));
+        }
+    });
+}
@@ -0,0 +1,353 @@
+// Colour maths for addressable LEDs.
+//
+// All integer, with 8 bit channels and 0-255 standing for 0.0-1.0
+// throughout: hues go once round the wheel from 0 to 255, and a blend
+// amount of 255 means all of the second colour.
+//
+// Brightness is best set before gamma correction, which maps what we
+// perceive onto the PWM duty the LEDs need, and the power limit goes last,
+// as it works on the current the LEDs will actually draw.
+
+use rgb::RGB8;
+
+pub const BLACK: RGB8 = RGB8::new(0, 0, 0);
+
+/// Hue, saturation and value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hsv {
+    pub h: u8,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    pub const fn new(h: u8, s: u8, v: u8) -> Self {
+        Self { h, s, v }
+    }
+
+    pub fn to_rgb(self) -> RGB8 {
+        let chroma = self.v as u32 * self.s as u32 / 255;
+        from_chroma(self.h, chroma, self.v as u32 - chroma)
+    }
+
+    pub fn from_rgb(color: RGB8) -> Self {
+        let (r, g, b) = (color.r as i32, color.g as i32, color.b as i32);
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        if delta == 0 {
+            return Self::new(0, 0, max as u8);
+        }
+
+        // Position round the wheel in 1/256ths of a sixth.
+        let sixths = if max == r {
+            (g - b) * 256 / delta
+        } else if max == g {
+            512 + (b - r) * 256 / delta
+        } else {
+            1024 + (r - g) * 256 / delta
+        };
+        let h = sixths.rem_euclid(1536) / 6;
+        Self::new(h as u8, (delta * 255 / max) as u8, max as u8)
+    }
+}
+
+impl From<Hsv> for RGB8 {
+    fn from(hsv: Hsv) -> Self {
+        hsv.to_rgb()
+    }
+}
+
+/// Hue, saturation and lightness. Lightness 255 is white whatever the
+/// hue, unlike value in `Hsv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hsl {
+    pub h: u8,
+    pub s: u8,
+    pub l: u8,
+}
+
+impl Hsl {
+    pub const fn new(h: u8, s: u8, l: u8) -> Self {
+        Self { h, s, l }
+    }
+
+    pub fn to_rgb(self) -> RGB8 {
+        let l = self.l as u32;
+        let chroma = (255 - (2 * l).abs_diff(255)) * self.s as u32 / 255;
+        from_chroma(self.h, chroma, l - chroma / 2)
+    }
+}
+
+impl From<Hsl> for RGB8 {
+    fn from(hsl: Hsl) -> Self {
+        hsl.to_rgb()
+    }
+}
+
+// The shared end of HSV and HSL: a hue, how colourful it is and how much
+// of every channel to add on top.
+fn from_chroma(hue: u8, chroma: u32, min: u32) -> RGB8 {
+    let sixths = hue as u32 * 6;
+    let x = chroma * (256 - (sixths % 512).abs_diff(256)) / 256;
+    let (r, g, b) = match sixths / 256 {
+        0 => (chroma, x, 0),
+        1 => (x, chroma, 0),
+        2 => (0, chroma, x),
+        3 => (0, x, chroma),
+        4 => (x, 0, chroma),
+        _ => (chroma, 0, x),
+    };
+    let channel = |c: u32| (c + min).min(255) as u8;
+    RGB8::new(channel(r), channel(g), channel(b))
+}
+
+/// Fully saturated colour round the wheel, red at 0.
+pub fn wheel(hue: u8) -> RGB8 {
+    Hsv::new(hue, 255, 255).to_rgb()
+}
+
+/// Multiplies every channel by `level`/255.
+pub fn scale(color: RGB8, level: u8) -> RGB8 {
+    let scale = |c: u8| (c as u16 * level as u16 / 255) as u8;
+    RGB8::new(scale(color.r), scale(color.g), scale(color.b))
+}
+
+pub fn lerp_u8(a: u8, b: u8, t: u8) -> u8 {
+    (a as i32 + (b as i32 - a as i32) * t as i32 / 255) as u8
+}
+
+/// `t` of 0 is all `a`, 255 all `b`.
+pub fn lerp(a: RGB8, b: RGB8, t: u8) -> RGB8 {
+    RGB8::new(lerp_u8(a.r, b.r, t), lerp_u8(a.g, b.g, t), lerp_u8(a.b, b.b, t))
+}
+
+/// Adds the light of two colours, clipping at full brightness.
+pub fn add(a: RGB8, b: RGB8) -> RGB8 {
+    RGB8::new(a.r.saturating_add(b.r), a.g.saturating_add(b.g), a.b.saturating_add(b.b))
+}
+
+/// Draws `top` over `bottom` with opacity `alpha`.
+pub fn blend(bottom: RGB8, top: RGB8, alpha: u8) -> RGB8 {
+    lerp(bottom, top, alpha)
+}
+
+/// Splits the grey out of a colour, for RGBW LEDs: the white channel takes
+/// the level all three share and the rest stays as colour.
+pub fn split_white(color: RGB8) -> (RGB8, u8) {
+    let white = color.r.min(color.g).min(color.b);
+    (RGB8::new(color.r - white, color.g - white, color.b - white), white)
+}
+
+/// Gamma 2.8, a good fit for WS2812s.
+pub static GAMMA: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2,
+    2, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 5, 5, 5,
+    5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10,
+    10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 14, 14, 15, 15, 16, 16,
+    17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22, 23, 24, 24, 25,
+    25, 26, 27, 27, 28, 29, 29, 30, 31, 32, 32, 33, 34, 35, 35, 36,
+    37, 38, 39, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 50,
+    51, 52, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 66, 67, 68,
+    69, 70, 72, 73, 74, 75, 77, 78, 79, 81, 82, 83, 85, 86, 87, 89,
+    90, 92, 93, 95, 96, 98, 99, 101, 102, 104, 105, 107, 109, 110, 112, 114,
+    115, 117, 119, 120, 122, 124, 126, 127, 129, 131, 133, 135, 137, 138, 140, 142,
+    144, 146, 148, 150, 152, 154, 156, 158, 160, 162, 164, 167, 169, 171, 173, 175,
+    177, 180, 182, 184, 186, 189, 191, 193, 196, 198, 200, 203, 205, 208, 210, 213,
+    215, 218, 220, 223, 225, 228, 231, 233, 236, 239, 241, 244, 247, 249, 252, 255,
+];
+
+pub fn gamma(color: RGB8) -> RGB8 {
+    RGB8::new(GAMMA[color.r as usize], GAMMA[color.g as usize], GAMMA[color.b as usize])
+}
+
+/// Brightness then gamma correction, for a whole frame.
+pub fn correct(pixels: &mut [RGB8], brightness: u8) {
+    for pixel in pixels {
+        *pixel = gamma(scale(*pixel, brightness));
+    }
+}
+
+/// How much current a strip draws and how much it's allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerLimit {
+    /// Drawn by one channel at full brightness.
+    pub channel_ma: u32,
+    /// Drawn by each pixel even when it's off.
+    pub idle_ma: u32,
+    /// The most the whole strip may draw.
+    pub max_ma: u32,
+}
+
+impl PowerLimit {
+    /// WS2812B figures, allowing `max_ma` for the strip.
+    pub const fn ws2812b(max_ma: u32) -> Self {
+        Self { channel_ma: 20, idle_ma: 1, max_ma }
+    }
+
+    /// Roughly what `pixels` would draw, in mA.
+    pub fn current(&self, pixels: &[RGB8]) -> u32 {
+        let channels: u32 = pixels.iter().map(|p| p.r as u32 + p.g as u32 + p.b as u32).sum();
+        channels * self.channel_ma / 255 + pixels.len() as u32 * self.idle_ma
+    }
+
+    /// What to `scale` `pixels` by to keep within `max_ma`, 255 if they
+    /// already are.
+    pub fn level(&self, pixels: &[RGB8]) -> u8 {
+        let idle = pixels.len() as u32 * self.idle_ma;
+        let lit = self.current(pixels) - idle;
+        let allowed = self.max_ma.saturating_sub(idle);
+        if lit <= allowed {
+            255
+        } else {
+            (allowed * 255 / lit) as u8
+        }
+    }
+
+    /// Dims `pixels` evenly until they're within `max_ma`.
+    pub fn limit(&self, pixels: &mut [RGB8]) {
+        let level = self.level(pixels);
+        if level < 255 {
+            for pixel in pixels {
+                *pixel = scale(*pixel, level);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: RGB8 = RGB8::new(255, 0, 0);
+    const WHITE: RGB8 = RGB8::new(255, 255, 255);
+
+    // Integer hues don't land exactly on the secondaries, so allow a
+    // little either way.
+    fn assert_near(actual: RGB8, expected: RGB8) {
+        let near = |a: u8, b: u8| a.abs_diff(b) <= 3;
+        assert!(
+            near(actual.r, expected.r) && near(actual.g, expected.g) && near(actual.b, expected.b),
+            "{actual:?} isn't near {expected:?}"
+        );
+    }
+
+    #[test]
+    fn wheel_goes_through_the_primaries_and_secondaries() {
+        assert_eq!(wheel(0), RED);
+        assert_near(wheel(43), RGB8::new(255, 255, 0));
+        assert_near(wheel(85), RGB8::new(0, 255, 0));
+        assert_near(wheel(128), RGB8::new(0, 255, 255));
+        assert_near(wheel(171), RGB8::new(0, 0, 255));
+        assert_near(wheel(213), RGB8::new(255, 0, 255));
+        // A step short of going all the way round.
+        assert_eq!(wheel(255), RGB8::new(255, 0, 5));
+    }
+
+    #[test]
+    fn hsv_saturation_and_value() {
+        assert_eq!(Hsv::new(0, 0, 255).to_rgb(), WHITE);
+        assert_eq!(Hsv::new(100, 0, 128).to_rgb(), RGB8::new(128, 128, 128));
+        assert_eq!(Hsv::new(0, 255, 0).to_rgb(), BLACK);
+        assert_eq!(Hsv::new(0, 255, 128).to_rgb(), RGB8::new(128, 0, 0));
+        assert_eq!(RGB8::from(Hsv::new(0, 255, 255)), RED);
+    }
+
+    #[test]
+    fn hsv_round_trips_the_primaries() {
+        for color in [RED, RGB8::new(0, 255, 0), RGB8::new(0, 0, 255), WHITE, BLACK] {
+            assert_near(Hsv::from_rgb(color).to_rgb(), color);
+        }
+        assert_eq!(Hsv::from_rgb(RED), Hsv::new(0, 255, 255));
+        assert_eq!(Hsv::from_rgb(RGB8::new(0, 0, 255)).h, 170);
+    }
+
+    #[test]
+    fn hsl_goes_from_black_through_the_hue_to_white() {
+        assert_eq!(Hsl::new(0, 255, 0).to_rgb(), BLACK);
+        assert_near(Hsl::new(0, 255, 128).to_rgb(), RED);
+        assert_near(Hsl::new(85, 255, 128).to_rgb(), RGB8::new(0, 255, 0));
+        assert_eq!(Hsl::new(0, 255, 255).to_rgb(), WHITE);
+        assert_eq!(Hsl::new(171, 0, 64).to_rgb(), RGB8::new(64, 64, 64));
+    }
+
+    #[test]
+    fn lerp_ends_on_each_colour() {
+        let a = RGB8::new(10, 200, 30);
+        let b = RGB8::new(250, 0, 30);
+        assert_eq!(lerp(a, b, 0), a);
+        assert_eq!(lerp(a, b, 255), b);
+        assert_eq!(lerp(a, b, 128), RGB8::new(130, 100, 30));
+        assert_eq!(lerp_u8(255, 0, 255), 0);
+        assert_eq!(lerp_u8(0, 255, 255), 255);
+    }
+
+    #[test]
+    fn add_saturates() {
+        assert_eq!(add(RGB8::new(200, 100, 0), RGB8::new(100, 100, 10)), RGB8::new(255, 200, 10));
+        assert_eq!(add(WHITE, WHITE), WHITE);
+        assert_eq!(add(RED, BLACK), RED);
+    }
+
+    #[test]
+    fn blend_is_opacity() {
+        let bottom = RGB8::new(0, 0, 255);
+        assert_eq!(blend(bottom, RED, 0), bottom);
+        assert_eq!(blend(bottom, RED, 255), RED);
+        assert_eq!(blend(bottom, RED, 51), RGB8::new(51, 0, 204));
+    }
+
+    #[test]
+    fn split_white_takes_the_shared_level() {
+        assert_eq!(split_white(WHITE), (BLACK, 255));
+        assert_eq!(split_white(RED), (RED, 0));
+        assert_eq!(split_white(RGB8::new(30, 80, 20)), (RGB8::new(10, 60, 0), 20));
+    }
+
+    #[test]
+    fn gamma_keeps_the_ends_and_only_goes_up() {
+        assert_eq!(GAMMA[0], 0);
+        assert_eq!(GAMMA[255], 255);
+        assert!(GAMMA.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(gamma(BLACK), BLACK);
+        assert_eq!(gamma(WHITE), WHITE);
+        // Mid grey comes out well under half duty.
+        assert!(gamma(RGB8::new(128, 128, 128)).r < 64);
+    }
+
+    #[test]
+    fn correct_scales_before_gamma() {
+        let mut pixels = [WHITE, RED];
+        correct(&mut pixels, 128);
+        assert_eq!(pixels, [gamma(RGB8::new(128, 128, 128)), gamma(RGB8::new(128, 0, 0))]);
+    }
+
+    #[test]
+    fn current_counts_channels_and_idle() {
+        let limit = PowerLimit::ws2812b(500);
+        assert_eq!(limit.current(&[BLACK; 15]), 15);
+        assert_eq!(limit.current(&[RED; 15]), 15 * 20 + 15);
+        assert_eq!(limit.current(&[WHITE; 15]), 15 * 60 + 15);
+    }
+
+    #[test]
+    fn full_white_is_limited_to_the_budget() {
+        let limit = PowerLimit::ws2812b(500);
+        let mut pixels = [WHITE; 15];
+        let level = limit.level(&pixels);
+        assert!(level < 255);
+        limit.limit(&mut pixels);
+        assert!(limit.current(&pixels) <= 500, "{} mA", limit.current(&pixels));
+        assert!(pixels.iter().all(|&pixel| pixel == scale(WHITE, level)));
+    }
+
+    #[test]
+    fn frames_within_the_budget_are_left_alone() {
+        let limit = PowerLimit::ws2812b(500);
+        let mut pixels = [RED; 15];
+        assert_eq!(limit.level(&pixels), 255);
+        limit.limit(&mut pixels);
+        assert_eq!(pixels, [RED; 15]);
+    }
+}
@@ -0,0 +1,211 @@
+// Every sensor from the calendar on one OLED page.
+//
+// Laid out for the 128x32 panel: the DS18B20 temperature in large digits
+// with a sparkline of its recent history underneath, and down the right
+// hand side the PIR and break beam icons, the light level and a gauge for
+// the potentiometer. On a 128x64 panel it sits in the top half.
+//
+// `Dashboard` remembers what it last drew and only redraws the parts whose
+// reading changed, each into its own cleared region. Nothing is visible
+// until the flush, so there's no flicker, and the flush only sends the
+// regions that moved.
+
+use core::fmt::Write;
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Alignment;
+use heapless::{HistoryBuffer, String};
+
+use crate::display::{Flush, FontSize, Icon, Screen, ICON_SIZE};
+
+/// Temperatures kept for the sparkline, one per pixel column.
+pub const HISTORY_LEN: usize = 64;
+
+// The sparkline never zooms in closer than this many degrees.
+const HISTORY_MIN_SPAN: f32 = 1.0;
+
+const TEMPERATURE_AREA: Rectangle = Rectangle::new(Point::new(0, 0), Size::new(64, 20));
+const HISTORY_AREA: Rectangle = Rectangle::new(Point::new(0, 22), Size::new(64, 10));
+const MOTION_AREA: Rectangle = Rectangle::new(Point::new(68, 0), Size::new(ICON_SIZE, ICON_SIZE));
+const BEAM_AREA: Rectangle = Rectangle::new(Point::new(80, 0), Size::new(ICON_SIZE, ICON_SIZE));
+const LIGHT_AREA: Rectangle = Rectangle::new(Point::new(68, 10), Size::new(60, 13));
+const POTENTIOMETER_AREA: Rectangle = Rectangle::new(Point::new(68, 25), Size::new(60, 7));
+
+/// Readings from the sensors that are quick to read, see
+/// `Dashboard::record_temperature` for the DS18B20.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Readings {
+    /// Percent.
+    pub light: u8,
+    /// Percent.
+    pub potentiometer: u8,
+    pub motion: bool,
+    pub beam_broken: bool,
+}
+
+/// Converts a raw 12 bit ADC reading to a percentage.
+pub fn adc_percent(raw: u16) -> u8 {
+    (raw.min(4095) as u32 * 100 / 4095) as u8
+}
+
+#[derive(Clone, Copy)]
+struct Shown {
+    temperature: Option<f32>,
+    readings: Readings,
+}
+
+pub struct Dashboard {
+    temperature: Option<f32>,
+    readings: Readings,
+    history: HistoryBuffer<f32, HISTORY_LEN>,
+    history_changed: bool,
+    shown: Option<Shown>,
+}
+
+impl Dashboard {
+    pub const fn new() -> Self {
+        Self {
+            temperature: None,
+            readings: Readings { light: 0, potentiometer: 0, motion: false, beam_broken: false },
+            history: HistoryBuffer::new(),
+            history_changed: false,
+            shown: None,
+        }
+    }
+
+    pub fn readings(&self) -> Readings {
+        self.readings
+    }
+
+    pub fn update(&mut self, readings: Readings) {
+        self.readings = readings;
+    }
+
+    pub fn temperature(&self) -> Option<f32> {
+        self.temperature
+    }
+
+    /// Stores a new DS18B20 reading in degrees C, `None` if it didn't
+    /// answer. Each call adds a point to the sparkline.
+    pub fn record_temperature(&mut self, celsius: Option<f32>) {
+        self.temperature = celsius;
+        if let Some(celsius) = celsius {
+            self.history.write(celsius);
+            self.history_changed = true;
+        }
+    }
+
+    /// Makes the next `draw` redraw everything, for when something else has
+    /// been drawn over the screen.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    /// Draws whatever changed since the last call. Doesn't flush.
+    pub fn draw<D: Flush>(&mut self, screen: &mut Screen<D>) -> Result<(), D::Error> {
+        let now = Shown { temperature: self.temperature, readings: self.readings };
+        let all = self.shown.is_none();
+        let old = self.shown.unwrap_or(now);
+        if all {
+            screen.clear()?;
+        }
+
+        if all || old.temperature != now.temperature {
+            let mut text: String<8> = String::new();
+            match self.temperature {
+                Some(celsius) => write!(text, "{:.1}C", celsius).ok(),
+                None => text.push_str("--.-C").ok(),
+            };
+            screen.clear_region(TEMPERATURE_AREA)?;
+            screen.text(&text, TEMPERATURE_AREA.top_left, FontSize::Large, Alignment::Left)?;
+        }
+
+        if all || self.history_changed {
+            screen.sparkline(HISTORY_AREA, self.history.oldest_ordered().copied(), HISTORY_MIN_SPAN)?;
+            self.history_changed = false;
+        }
+
+        if all || old.readings.motion != now.readings.motion {
+            screen.clear_region(MOTION_AREA)?;
+            if now.readings.motion {
+                screen.icon(Icon::Motion, MOTION_AREA.top_left)?;
+            }
+        }
+
+        if all || old.readings.beam_broken != now.readings.beam_broken {
+            screen.clear_region(BEAM_AREA)?;
+            if now.readings.beam_broken {
+                screen.icon(Icon::Beam, BEAM_AREA.top_left)?;
+            }
+        }
+
+        if all || old.readings.light != now.readings.light {
+            let mut text: String<8> = String::new();
+            write!(text, "{}%", now.readings.light).ok();
+            screen.clear_region(LIGHT_AREA)?;
+            screen.icon(Icon::Light, LIGHT_AREA.top_left + Point::new(0, 2))?;
+            screen.text(&text, LIGHT_AREA.top_left + Point::new(12, 0), FontSize::Medium, Alignment::Left)?;
+        }
+
+        if all || old.readings.potentiometer != now.readings.potentiometer {
+            screen.gauge(POTENTIOMETER_AREA, now.readings.potentiometer as f32 / 100.0)?;
+        }
+
+        self.shown = Some(now);
+        Ok(())
+    }
+}
+
+impl Default for Dashboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::{FrameBuffer128x32, FrameBuffer128x64};
+
+    fn dashboard() -> Dashboard {
+        let mut dashboard = Dashboard::new();
+        for celsius in [20.0, 20.5, 21.25, 21.0, 21.5] {
+            dashboard.record_temperature(Some(celsius));
+        }
+        dashboard.update(Readings { light: 63, potentiometer: 40, motion: true, beam_broken: false });
+        dashboard
+    }
+
+    #[test]
+    fn dashboard_128x32() {
+        let mut screen = Screen::new(FrameBuffer128x32::new());
+        dashboard().draw(&mut screen).unwrap();
+        screen.target().assert_golden(include_str!("golden/dashboard_128x32.txt"));
+    }
+
+    #[test]
+    fn dashboard_128x64() {
+        let mut screen = Screen::new(FrameBuffer128x64::new());
+        dashboard().draw(&mut screen).unwrap();
+        screen.target().assert_golden(include_str!("golden/dashboard_128x64.txt"));
+    }
+
+    #[test]
+    fn partial_redraws_match_a_full_one() {
+        let mut dashboard = Dashboard::new();
+        let mut screen = Screen::new(FrameBuffer128x32::new());
+        dashboard.draw(&mut screen).unwrap();
+        dashboard.update(Readings { light: 5, potentiometer: 90, motion: false, beam_broken: true });
+        dashboard.draw(&mut screen).unwrap();
+
+        // Brought round to the same state as `dashboard()` a bit at a time.
+        for celsius in [20.0, 20.5, 21.25, 21.0, 21.5] {
+            dashboard.record_temperature(Some(celsius));
+            dashboard.draw(&mut screen).unwrap();
+        }
+        dashboard.update(self::dashboard().readings());
+        dashboard.draw(&mut screen).unwrap();
+        screen.target().assert_golden(include_str!("golden/dashboard_128x32.txt"));
+    }
+}
@@ -0,0 +1,266 @@
+// In-memory log of sensor readings, exported as CSV.
+//
+// The log only ever appends, once it's full new readings are counted as
+// dropped. That way a prefix of the log never changes, which lets the USB
+// disk export a stable snapshot while recording carries on.
+
+use core::fmt::Write;
+
+use heapless::{String, Vec};
+
+use crate::fat12::{self, Fat12Image};
+use crate::protocol::Sensor;
+use crate::usb_msc::{BlockDevice, ReadError, BLOCK_SIZE};
+
+pub const CSV_HEADER: &str = "time_ms,sensor,value\n";
+pub const CSV_FILE_NAME: &str = "DATA.CSV";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    pub time_ms: u32,
+    pub sensor: Sensor,
+    pub value: i32,
+}
+
+impl Record {
+    fn csv_line(&self) -> String<48> {
+        let mut line = String::new();
+        // 48 bytes fits the longest possible line, this can't fail
+        let _ = writeln!(line, "{},{},{}", self.time_ms, self.sensor.name(), self.value);
+        line
+    }
+}
+
+pub struct DataLog<const N: usize> {
+    records: Vec<Record, N>,
+    // Where each record's CSV line ends, header included, so reading the
+    // file can start straight at the right record.
+    ends: Vec<u32, N>,
+    dropped: u32,
+}
+
+impl<const N: usize> DataLog<N> {
+    pub const fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            ends: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Returns false if the log is full and the record was dropped.
+    pub fn push(&mut self, record: Record) -> bool {
+        let end = self.csv_len(self.records.len()) + record.csv_line().len() as u32;
+        if self.records.push(record).is_err() {
+            self.dropped = self.dropped.saturating_add(1);
+            return false;
+        }
+        // Same capacity as `records`, so there's room.
+        let _ = self.ends.push(end);
+        true
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Length in bytes of the CSV rendering of the first `count` records,
+    /// header included.
+    pub fn csv_len(&self, count: usize) -> u32 {
+        match count.min(self.ends.len()) {
+            0 => CSV_HEADER.len() as u32,
+            n => self.ends[n - 1],
+        }
+    }
+
+    /// Fills `buf` with the CSV rendering of the first `count` records
+    /// starting at byte `offset`. Anything past the end of the CSV is left
+    /// untouched.
+    pub fn read_csv(&self, count: usize, offset: u32, buf: &mut [u8]) {
+        let end = offset + buf.len() as u32;
+        // Copies the overlap of a chunk at `start` with the buffer.
+        let mut copy = |start: u32, chunk: &[u8]| {
+            let from = start.max(offset);
+            let to = (start + chunk.len() as u32).min(end);
+            if from < to {
+                let src = &chunk[(from - start) as usize..(to - start) as usize];
+                buf[(from - offset) as usize..(to - offset) as usize].copy_from_slice(src);
+            }
+        };
+
+        copy(0, CSV_HEADER.as_bytes());
+        let count = count.min(self.records.len());
+        let first = self.ends[..count].partition_point(|&line_end| line_end <= offset);
+        for index in first..count {
+            let start = self.csv_len(index);
+            if start >= end {
+                break;
+            }
+            copy(start, self.records[index].csv_line().as_bytes());
+        }
+    }
+}
+
+impl<const N: usize> Default for DataLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read-only disk holding the log as DATA.CSV. The file holds the records
+/// there were when the host last checked the disk was ready, and is told
+/// the disk has changed if there are more.
+pub struct CsvDisk<const N: usize> {
+    log: DataLog<N>,
+    snapshot: usize,
+    image: Fat12Image,
+}
+
+impl<const N: usize> CsvDisk<N> {
+    pub fn new(log: DataLog<N>) -> Self {
+        let mut disk = Self {
+            log,
+            snapshot: 0,
+            image: Fat12Image::new("CODEMAS", CSV_FILE_NAME, 0),
+        };
+        disk.refresh();
+        disk
+    }
+
+    pub fn log(&mut self) -> &mut DataLog<N> {
+        &mut self.log
+    }
+
+    pub fn refresh(&mut self) {
+        self.snapshot = self.log.records().len();
+        let size = self.log.csv_len(self.snapshot);
+        self.image = Fat12Image::new("CODEMAS", CSV_FILE_NAME, size);
+    }
+}
+
+impl<const N: usize> BlockDevice for CsvDisk<N> {
+    fn block_count(&self) -> u32 {
+        fat12::TOTAL_SECTORS
+    }
+
+    fn read_block(&mut self, lba: u32, buf: &mut [u8; BLOCK_SIZE]) -> Result<(), ReadError> {
+        let (log, snapshot) = (&self.log, self.snapshot);
+        self.image.read_sector(lba, buf, |offset, data| log.read_csv(snapshot, offset, data));
+        Ok(())
+    }
+
+    fn changed(&mut self) -> bool {
+        if self.log.records().len() == self.snapshot {
+            return false;
+        }
+        self.refresh();
+        true
+    }
+
+    fn reset(&mut self) {
+        self.refresh();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::string::String;
+
+    // From the layout in fat12.rs.
+    const ROOT_DIR: u32 = 13;
+    const FILE_START: u32 = 14;
+
+    fn log_of(count: u32) -> DataLog<64> {
+        let mut log = DataLog::new();
+        for i in 0..count {
+            let sensor = if i % 2 == 0 { Sensor::Potentiometer } else { Sensor::Temperature };
+            log.push(Record { time_ms: i * 1000, sensor, value: i as i32 * 37 - 500 });
+        }
+        log
+    }
+
+    fn expected(log: &DataLog<64>, count: usize) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        for r in &log.records()[..count] {
+            csv += &std::format!("{},{},{}\n", r.time_ms, r.sensor.name(), r.value);
+        }
+        csv
+    }
+
+    #[test]
+    fn csv_len_counts_every_line() {
+        let log = log_of(40);
+        for count in [0, 1, 17, 40] {
+            assert_eq!(log.csv_len(count) as usize, expected(&log, count).len());
+        }
+    }
+
+    #[test]
+    fn reads_start_anywhere() {
+        let log = log_of(40);
+        let csv = expected(&log, 40);
+        for len in [1, 7, 64, 512] {
+            for offset in (0..csv.len()).step_by(5) {
+                let mut buf = std::vec![0xFF; len];
+                log.read_csv(40, offset as u32, &mut buf);
+                let have = (csv.len() - offset).min(len);
+                assert_eq!(&buf[..have], &csv.as_bytes()[offset..offset + have], "offset {offset} len {len}");
+                assert!(buf[have..].iter().all(|&b| b == 0xFF));
+            }
+        }
+    }
+
+    #[test]
+    fn reads_stop_at_the_snapshot() {
+        let log = log_of(40);
+        let csv = expected(&log, 10);
+        let mut buf = [0u8; 2048];
+        log.read_csv(10, 0, &mut buf);
+        assert_eq!(&buf[..csv.len()], csv.as_bytes());
+        assert!(buf[csv.len()..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn full_log_drops() {
+        let mut log: DataLog<2> = DataLog::new();
+        let record = Record { time_ms: 0, sensor: Sensor::Light, value: 1 };
+        assert!(log.push(record));
+        assert!(log.push(record));
+        assert!(!log.push(record));
+        assert_eq!(log.dropped(), 1);
+        assert_eq!(log.csv_len(3), log.csv_len(2));
+    }
+
+    #[test]
+    fn disk_changes_when_there_are_new_records() {
+        let mut disk = CsvDisk::new(log_of(3));
+        assert!(!disk.changed());
+
+        let mut root = [0u8; BLOCK_SIZE];
+        let size = |disk: &mut CsvDisk<64>, root: &mut [u8; BLOCK_SIZE]| {
+            disk.read_block(ROOT_DIR, root).unwrap();
+            u32::from_le_bytes([root[60], root[61], root[62], root[63]])
+        };
+        let before = size(&mut disk, &mut root);
+        assert_eq!(before, disk.log().csv_len(3));
+
+        disk.log().push(Record { time_ms: 5000, sensor: Sensor::Pir, value: 1 });
+        // Still the old snapshot until the host next asks.
+        assert_eq!(size(&mut disk, &mut root), before);
+        assert!(disk.changed());
+        assert!(!disk.changed());
+        assert_eq!(size(&mut disk, &mut root), disk.log().csv_len(4));
+
+        let mut data = [0u8; BLOCK_SIZE];
+        disk.read_block(FILE_START, &mut data).unwrap();
+        let csv = expected(&log_of(3), 3) + "5000,pir,1\n";
+        assert_eq!(&data[..csv.len()], csv.as_bytes());
+    }
+}
@@ -0,0 +1,204 @@
+// defmt global logger that buffers encoded frames in RAM so they can be sent
+// out over a USB CDC port.
+//
+// Logging only ever touches the buffer, the firmware moves bytes out of it
+// with `drain` from wherever it services USB (usually the USBCTRL_IRQ
+// handler). Frames are rzcobs encoded and end in a 0x00. If the buffer
+// fills up, the rest of that frame is dropped and the next frame to start
+// puts the missing 0x00 in first, so the host decoder only loses the
+// damaged frame and picks up again at the one after.
+//
+// Decode on the host with `codemas-host log <port> <elf>`.
+
+use core::cell::RefCell;
+use core::ptr::addr_of_mut;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use critical_section::{Mutex, RestoreState};
+use heapless::Deque;
+
+const BUF_SIZE: usize = 1024;
+
+struct Buffer {
+    bytes: Deque<u8, BUF_SIZE>,
+    dropped: u32,
+    // The last frame lost bytes and hasn't been ended yet.
+    truncated: bool,
+}
+
+impl Buffer {
+    const fn new() -> Self {
+        Self { bytes: Deque::new(), dropped: 0, truncated: false }
+    }
+
+    // Ends a frame cut short by an overflow before the next one starts.
+    // If there's still no room, the next frame is dropped as well.
+    fn start_frame(&mut self) {
+        if self.truncated && self.bytes.push_back(0).is_ok() {
+            self.truncated = false;
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.truncated || self.bytes.push_back(byte).is_err() {
+                self.truncated = true;
+                self.dropped = self.dropped.saturating_add(1);
+            }
+        }
+    }
+
+    fn drain(&mut self, mut write: impl FnMut(&[u8]) -> usize) {
+        while !self.bytes.is_empty() {
+            let chunk = self.bytes.as_slices().0;
+            let len = chunk.len();
+            let written = write(chunk).min(len);
+            for _ in 0..written {
+                self.bytes.pop_front();
+            }
+            if written < len {
+                break;
+            }
+        }
+    }
+}
+
+static BUFFER: Mutex<RefCell<Buffer>> = Mutex::new(RefCell::new(Buffer::new()));
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+static mut CS_RESTORE: RestoreState = RestoreState::invalid();
+static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        let restore = unsafe { critical_section::acquire() };
+
+        if TAKEN.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly")
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+
+        // Safety: we're inside the critical section and hold TAKEN, nothing
+        // else can touch these until release.
+        unsafe {
+            CS_RESTORE = restore;
+            critical_section::with(|cs| BUFFER.borrow_ref_mut(cs).start_frame());
+            (*addr_of_mut!(ENCODER)).start_frame(push);
+        }
+    }
+
+    unsafe fn flush() {
+        // Nothing to do, bytes leave the buffer through `drain`.
+    }
+
+    unsafe fn release() {
+        (*addr_of_mut!(ENCODER)).end_frame(push);
+        TAKEN.store(false, Ordering::Relaxed);
+        let restore = CS_RESTORE;
+        critical_section::release(restore);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        (*addr_of_mut!(ENCODER)).write(bytes, push);
+    }
+}
+
+fn push(bytes: &[u8]) {
+    critical_section::with(|cs| BUFFER.borrow_ref_mut(cs).push(bytes));
+}
+
+/// Hands buffered log data to `write`, which returns how many bytes it
+/// accepted. Stops once `write` takes less than it was offered.
+pub fn drain(write: impl FnMut(&[u8]) -> usize) {
+    critical_section::with(|cs| BUFFER.borrow_ref_mut(cs).drain(write));
+}
+
+/// Number of encoded bytes lost because the buffer was full.
+pub fn dropped() -> u32 {
+    critical_section::with(|cs| BUFFER.borrow_ref(cs).dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    // One whole frame, as the logger writes any but the first. The very
+    // first also has a 0x00 in front.
+    fn frame(data: &[u8]) -> Vec<u8> {
+        let mut encoder = defmt::Encoder::new();
+        encoder.start_frame(|_| {});
+        encoder.end_frame(|_| {});
+        let mut out = Vec::new();
+        encoder.start_frame(|bytes| out.extend_from_slice(bytes));
+        encoder.write(data, |bytes| out.extend_from_slice(bytes));
+        encoder.end_frame(|bytes| out.extend_from_slice(bytes));
+        out
+    }
+
+    fn log(buffer: &mut Buffer, data: &[u8]) {
+        buffer.start_frame();
+        buffer.push(&frame(data));
+    }
+
+    fn contents(buffer: &mut Buffer) -> Vec<u8> {
+        let mut out = Vec::new();
+        buffer.drain(|bytes| {
+            out.extend_from_slice(bytes);
+            bytes.len()
+        });
+        out
+    }
+
+    #[test]
+    fn frames_end_in_zero() {
+        let encoded = frame(&[1, 2, 0, 3]);
+        assert_eq!(encoded.last(), Some(&0));
+        assert_eq!(encoded.iter().filter(|&&byte| byte == 0).count(), 1);
+    }
+
+    #[test]
+    fn frame_after_an_overflow_still_decodes() {
+        let mut buffer = Buffer::new();
+        let big = [0x55; BUF_SIZE];
+        log(&mut buffer, &[1, 2, 3]);
+        log(&mut buffer, &big);
+        assert!(buffer.dropped > 0);
+
+        // The host reads everything out, then the next frame goes in.
+        let before = contents(&mut buffer);
+        log(&mut buffer, &[4, 5, 6]);
+        let after = contents(&mut buffer);
+
+        // The first frame comes through whole, the big one is cut short
+        // and the next one is delimited from it.
+        let first = frame(&[1, 2, 3]);
+        assert_eq!(before[..first.len()], first[..]);
+        assert_ne!(before.last(), Some(&0));
+        assert_eq!(after[0], 0);
+        assert_eq!(after[1..], frame(&[4, 5, 6])[..]);
+    }
+
+    #[test]
+    fn frames_are_dropped_whole_until_there_is_room() {
+        let mut buffer = Buffer::new();
+        log(&mut buffer, &[0x55; BUF_SIZE]);
+        let full = buffer.bytes.len();
+        assert_eq!(full, BUF_SIZE);
+
+        // Still full, this one goes too rather than running on.
+        log(&mut buffer, &[4, 5, 6]);
+        assert_eq!(buffer.bytes.len(), full);
+
+        contents(&mut buffer);
+        log(&mut buffer, &[7, 8]);
+        let mut expected = std::vec![0];
+        expected.extend(frame(&[7, 8]));
+        assert_eq!(contents(&mut buffer), expected);
+    }
+}
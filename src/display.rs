@@ -0,0 +1,238 @@
+// Drawing on the SSD1306 OLED through embedded-graphics.
+//
+// `Screen` wraps anything that can be drawn on in black and white and
+// flushed, so project code draws into a `Screen` and doesn't care whether
+// it's the real panel or something else. With the ssd1306 buffered graphics
+// mode only the bounding box of pixels changed since the last flush is sent
+// over I2C, so redrawing a small region is cheap.
+
+use display_interface::{DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::image::{Image, ImageRaw};
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_5X8, FONT_6X10, FONT_8X13};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+use ssd1306::mode::BufferedGraphicsMode;
+use ssd1306::size::DisplaySize;
+use ssd1306::Ssd1306;
+
+use crate::text;
+
+/// A monochrome draw target whose contents can be pushed to the panel.
+pub trait Flush: DrawTarget<Color = BinaryColor> {
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<DI, SIZE> Flush for Ssd1306<DI, SIZE, BufferedGraphicsMode<SIZE>>
+where
+    DI: WriteOnlyDataCommand,
+    SIZE: DisplaySize,
+{
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        Ssd1306::flush(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontSize {
+    /// Fits on an 8 pixel high LED matrix.
+    Tiny,
+    Small,
+    Medium,
+    Large,
+}
+
+impl FontSize {
+    pub fn font(self) -> &'static MonoFont<'static> {
+        match self {
+            FontSize::Tiny => &FONT_5X8,
+            FontSize::Small => &FONT_6X10,
+            FontSize::Medium => &FONT_8X13,
+            FontSize::Large => &FONT_10X20,
+        }
+    }
+
+    /// Width and height of one character cell in pixels.
+    pub fn char_size(self) -> Size {
+        self.font().character_size
+    }
+
+    /// Characters that fit across `width` pixels.
+    pub fn columns(self, width: u32) -> usize {
+        (width / self.char_size().width) as usize
+    }
+
+    /// Lines that fit in `height` pixels.
+    pub fn rows(self, height: u32) -> usize {
+        (height / self.char_size().height) as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Icon {
+    Thermometer,
+    Light,
+    Motion,
+    Beam,
+    Tilt,
+    Usb,
+    Warning,
+}
+
+pub const ICON_SIZE: u32 = 8;
+
+impl Icon {
+    fn bitmap(self) -> &'static [u8; 8] {
+        match self {
+            Icon::Thermometer => &[0x18, 0x24, 0x24, 0x24, 0x3C, 0x7E, 0x7E, 0x3C],
+            Icon::Light => &[0x3C, 0x42, 0x81, 0x81, 0x42, 0x3C, 0x3C, 0x18],
+            Icon::Motion => &[0x18, 0x18, 0x3C, 0x5A, 0x18, 0x24, 0x42, 0x42],
+            Icon::Beam => &[0xC0, 0xC0, 0x00, 0xDB, 0xDB, 0x00, 0xC0, 0xC0],
+            Icon::Tilt => &[0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0xFF],
+            Icon::Usb => &[0x18, 0x3C, 0x18, 0x5A, 0x5A, 0x3C, 0x18, 0x18],
+            Icon::Warning => &[0x18, 0x18, 0x3C, 0x3C, 0x7E, 0x66, 0xFF, 0xFF],
+        }
+    }
+}
+
+pub struct Screen<D> {
+    target: D,
+}
+
+impl<D: Flush> Screen<D> {
+    pub fn new(target: D) -> Self {
+        Self { target }
+    }
+
+    pub fn target(&mut self) -> &mut D {
+        &mut self.target
+    }
+
+    pub fn release(self) -> D {
+        self.target
+    }
+
+    pub fn size(&self) -> Size {
+        self.target.bounding_box().size
+    }
+
+    pub fn clear(&mut self) -> Result<(), D::Error> {
+        self.target.clear(BinaryColor::Off)
+    }
+
+    /// Blanks a region so it can be redrawn without touching the rest of
+    /// the screen.
+    pub fn clear_region(&mut self, area: Rectangle) -> Result<(), D::Error> {
+        self.target.fill_solid(&area, BinaryColor::Off)
+    }
+
+    /// Draws text with its top edge at `position`. The x coordinate is the
+    /// left edge, centre or right edge depending on `alignment`. Returns the
+    /// position just after the text.
+    pub fn text(&mut self, text: &str, position: Point, size: FontSize, alignment: Alignment) -> Result<Point, D::Error> {
+        let character_style = MonoTextStyle::new(size.font(), BinaryColor::On);
+        let text_style = TextStyleBuilder::new()
+            .alignment(alignment)
+            .baseline(Baseline::Top)
+            .build();
+        Text::with_text_style(text, position, character_style, text_style).draw(&mut self.target)
+    }
+
+    /// Word wraps `text` to fit across `area` and draws as many lines as
+    /// fit, each aligned within `area`. Returns how many lines were drawn.
+    pub fn paragraph(&mut self, text: &str, area: Rectangle, size: FontSize, alignment: Alignment) -> Result<usize, D::Error> {
+        let x = match alignment {
+            Alignment::Left => area.top_left.x,
+            Alignment::Center => area.center().x,
+            Alignment::Right => area.top_left.x + area.size.width as i32,
+        };
+        let line_height = size.char_size().height as i32;
+        let lines = text::wrap(text, size.columns(area.size.width)).take(size.rows(area.size.height));
+
+        let mut drawn = 0;
+        for line in lines {
+            self.text(line, Point::new(x, area.top_left.y + drawn as i32 * line_height), size, alignment)?;
+            drawn += 1;
+        }
+        Ok(drawn)
+    }
+
+    pub fn line(&mut self, start: Point, end: Point, width: u32) -> Result<(), D::Error> {
+        Line::new(start, end)
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, width))
+            .draw(&mut self.target)
+    }
+
+    pub fn rect(&mut self, area: Rectangle, filled: bool) -> Result<(), D::Error> {
+        let style = if filled {
+            PrimitiveStyle::with_fill(BinaryColor::On)
+        } else {
+            PrimitiveStyle::with_stroke(BinaryColor::On, 1)
+        };
+        area.into_styled(style).draw(&mut self.target)
+    }
+
+    pub fn icon(&mut self, icon: Icon, position: Point) -> Result<(), D::Error> {
+        let raw = ImageRaw::<BinaryColor>::new(icon.bitmap(), ICON_SIZE);
+        Image::new(&raw, position).draw(&mut self.target)
+    }
+
+    /// Horizontal bar gauge, `value` is clamped to 0.0..=1.0.
+    pub fn gauge(&mut self, area: Rectangle, value: f32) -> Result<(), D::Error> {
+        self.clear_region(area)?;
+        self.rect(area, false)?;
+
+        let value = value.clamp(0.0, 1.0);
+        let inner = area.size.width.saturating_sub(4);
+        let filled = (inner as f32 * value) as u32;
+        if filled > 0 {
+            let bar = Rectangle::new(area.top_left + Point::new(2, 2), Size::new(filled, area.size.height.saturating_sub(4)));
+            self.rect(bar, true)?;
+        }
+        Ok(())
+    }
+
+    /// Line graph of `values` in `area`, one value per pixel column with the
+    /// newest at the right. Scaled to fit the values shown, but over at
+    /// least `min_span` so a reading that's barely moving stays flat.
+    pub fn sparkline<I>(&mut self, area: Rectangle, values: I, min_span: f32) -> Result<(), D::Error>
+    where
+        I: IntoIterator<Item = f32>,
+        I::IntoIter: Clone,
+    {
+        self.clear_region(area)?;
+
+        let values = values.into_iter();
+        let width = area.size.width as usize;
+        let count = values.clone().count();
+        if count == 0 || width == 0 || area.size.height == 0 {
+            return Ok(());
+        }
+        let values = values.skip(count.saturating_sub(width));
+
+        let (min, max) = values.clone().fold((f32::MAX, f32::MIN), |(min, max), v| (min.min(v), max.max(v)));
+        let span = (max - min).max(min_span).max(f32::EPSILON);
+        let low = (min + max - span) / 2.0;
+
+        let bottom = area.top_left.y + area.size.height as i32 - 1;
+        let x0 = area.top_left.x + (width - count.min(width)) as i32;
+        let scale = (area.size.height - 1) as f32 / span;
+        let point = |(i, v): (usize, f32)| Point::new(x0 + i as i32, bottom - ((v - low) * scale + 0.5) as i32);
+
+        let mut points = values.enumerate().map(point);
+        let mut last = points.next().unwrap();
+        self.target.draw_iter(core::iter::once(Pixel(last, BinaryColor::On)))?;
+        for next in points {
+            self.line(last, next, 1)?;
+            last = next;
+        }
+        Ok(())
+    }
+
+    /// Sends everything drawn since the last flush to the panel.
+    pub fn flush(&mut self) -> Result<(), D::Error> {
+        self.target.flush()
+    }
+}
@@ -0,0 +1,420 @@
+// Animations for addressable LED strips.
+//
+// Every effect is a pure function of pixel position and time: nothing is
+// remembered between frames, so a frame can be rendered for any time on the
+// host as easily as on the strip, and effects can be mixed pixel by pixel.
+// Anything that looks random (twinkle, fire) comes from hashing the pixel
+// index with a time slot, so it's the same every run.
+//
+// `Animator` is what firmware drives: it runs the effect clock at the
+// chosen speed, applies brightness and crossfades when the effect changes.
+
+use rgb::RGB8;
+
+use crate::color::{lerp, lerp_u8, scale, wheel, BLACK};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Solid(RGB8),
+    /// The whole colour wheel spread along the strip, cycling.
+    Rainbow,
+    /// Every third pixel lit, marching along.
+    TheaterChase(RGB8),
+    /// Random pixels fading in and out.
+    Twinkle(RGB8),
+    /// Flickering flames, hottest at the start of the strip.
+    Fire,
+    /// A bright head with a fading tail running along the strip.
+    Comet(RGB8),
+    /// Fades from one colour to the other and back along the strip,
+    /// scrolling slowly.
+    Gradient(RGB8, RGB8),
+    /// The whole strip slowly brightening and dimming.
+    Breathing(RGB8),
+}
+
+impl Effect {
+    /// Colour of pixel `i` on a strip `len` long, `t` ms into the effect.
+    pub fn pixel(&self, i: usize, len: usize, t: u32) -> RGB8 {
+        let len = len.max(1) as u32;
+        let i = i as u32;
+        match *self {
+            Effect::Solid(color) => color,
+            Effect::Rainbow => wheel((i * 256 / len + t / 10) as u8),
+            Effect::TheaterChase(color) => {
+                if (i + t / 100).is_multiple_of(3) {
+                    color
+                } else {
+                    BLACK
+                }
+            }
+            Effect::Twinkle(color) => {
+                const PERIOD: u32 = 2000;
+                // Each pixel gets its own phase so they don't all change
+                // together, then a quarter of them light each period.
+                let t = t.wrapping_add(hash(i, 0) % PERIOD);
+                let slot = t / PERIOD;
+                if hash(i, slot).is_multiple_of(4) {
+                    scale(color, triangle(t % PERIOD, PERIOD))
+                } else {
+                    BLACK
+                }
+            }
+            Effect::Fire => {
+                const STEP: u32 = 80;
+                let slot = t / STEP;
+                let noise = lerp_u8(hash(i, slot) as u8, hash(i, slot + 1) as u8, ((t % STEP) * 255 / STEP) as u8);
+                // Cooler towards the far end.
+                let base = 255 - i * 160 / len;
+                heat_color((base * (128 + noise as u32 / 2) / 255) as u8)
+            }
+            Effect::Comet(color) => {
+                let tail = (len / 3).max(3);
+                let head = (t / 40) % (len + tail);
+                match head.checked_sub(i) {
+                    Some(behind) if behind < tail => scale(color, (255 * (tail - behind) / tail) as u8),
+                    _ => BLACK,
+                }
+            }
+            Effect::Gradient(from, to) => {
+                let position = (i * 512 / len + t / 20) % 512;
+                lerp(from, to, triangle(position, 512))
+            }
+            Effect::Breathing(color) => {
+                let level = triangle(t % 4000, 4000) as u32;
+                // Squared so it lingers at the dim end like breathing does.
+                scale(color, (level * level / 255) as u8)
+            }
+        }
+    }
+
+    /// The same effect in another colour. Effects that make their own
+    /// colours don't change, `Gradient` changes the colour it starts from.
+    pub fn with_color(self, color: RGB8) -> Self {
+        match self {
+            Effect::Solid(_) => Effect::Solid(color),
+            Effect::TheaterChase(_) => Effect::TheaterChase(color),
+            Effect::Twinkle(_) => Effect::Twinkle(color),
+            Effect::Comet(_) => Effect::Comet(color),
+            Effect::Gradient(_, to) => Effect::Gradient(color, to),
+            Effect::Breathing(_) => Effect::Breathing(color),
+            Effect::Rainbow | Effect::Fire => self,
+        }
+    }
+
+    /// Renders a whole frame, `t` ms into the effect.
+    pub fn render(&self, t: u32, pixels: &mut [RGB8]) {
+        let len = pixels.len();
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            *pixel = self.pixel(i, len, t);
+        }
+    }
+}
+
+pub struct Animator {
+    effect: Effect,
+    // The effect being faded out.
+    previous: Option<Effect>,
+    speed: u16,
+    brightness: u8,
+    fade_ms: u32,
+    // Effect clock, scaled by speed.
+    t: u32,
+    last_now: Option<u32>,
+    changed_at: u32,
+}
+
+impl Animator {
+    pub const fn new(effect: Effect) -> Self {
+        Self {
+            effect,
+            previous: None,
+            speed: 100,
+            brightness: 255,
+            fade_ms: 500,
+            t: 0,
+            last_now: None,
+            changed_at: 0,
+        }
+    }
+
+    pub fn effect(&self) -> Effect {
+        self.effect
+    }
+
+    /// Switches effect, crossfading from the current one.
+    pub fn set_effect(&mut self, effect: Effect) {
+        if effect != self.effect {
+            self.previous = Some(self.effect);
+            self.effect = effect;
+            self.changed_at = self.last_now.unwrap_or(0);
+        }
+    }
+
+    /// Switches effect without a crossfade, for small changes made every
+    /// frame, like a colour following a knob, that would never finish
+    /// fading.
+    pub fn replace_effect(&mut self, effect: Effect) {
+        self.effect = effect;
+    }
+
+    /// Percent of normal speed, 0 freezes the animation.
+    pub fn set_speed(&mut self, percent: u16) {
+        self.speed = percent;
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// How long a change of effect takes to fade across, 0 to cut.
+    pub fn set_fade(&mut self, ms: u32) {
+        self.fade_ms = ms;
+    }
+
+    /// Renders the frame for `now`, in ms from any fixed starting point.
+    /// Changing speed only changes how fast the animation moves from here
+    /// on, it doesn't jump.
+    pub fn render(&mut self, now: u32, pixels: &mut [RGB8]) {
+        let elapsed = now.wrapping_sub(self.last_now.unwrap_or(now));
+        self.t = self.t.wrapping_add((elapsed as u64 * self.speed as u64 / 100) as u32);
+        self.last_now = Some(now);
+
+        let since_change = now.wrapping_sub(self.changed_at);
+        let fade = match self.previous {
+            Some(_) if since_change < self.fade_ms => (since_change * 255 / self.fade_ms) as u8,
+            _ => {
+                self.previous = None;
+                255
+            }
+        };
+
+        let len = pixels.len();
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let mut color = self.effect.pixel(i, len, self.t);
+            if let Some(previous) = self.previous {
+                color = lerp(previous.pixel(i, len, self.t), color, fade);
+            }
+            *pixel = scale(color, self.brightness);
+        }
+    }
+}
+
+// Black through red and yellow to white.
+fn heat_color(heat: u8) -> RGB8 {
+    let ramp = (heat % 86) * 3;
+    match heat {
+        0..=85 => RGB8::new(ramp, 0, 0),
+        86..=171 => RGB8::new(255, ramp, 0),
+        _ => RGB8::new(255, 255, ramp),
+    }
+}
+
+// 0 at the start and end of `period`, 255 halfway.
+fn triangle(t: u32, period: u32) -> u8 {
+    let half = (period / 2).max(1);
+    let rising = if t < half { t } else { period - t };
+    (rising.min(half) * 255 / half) as u8
+}
+
+// Scrambles two numbers into one, for randomness that's the same every
+// time for the same inputs.
+fn hash(a: u32, b: u32) -> u32 {
+    let mut x = a.wrapping_mul(0x9E37_79B9) ^ b.wrapping_mul(0x85EB_CA6B);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7FEB_352D);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846C_A68B);
+    x ^ (x >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: RGB8 = RGB8::new(255, 0, 0);
+    const BLUE: RGB8 = RGB8::new(0, 0, 255);
+    const WHITE: RGB8 = RGB8::new(255, 255, 255);
+
+    fn frame<const N: usize>(animator: &mut Animator, now: u32) -> [RGB8; N] {
+        let mut pixels = [BLACK; N];
+        animator.render(now, &mut pixels);
+        pixels
+    }
+
+    fn effect_frame<const N: usize>(effect: Effect, t: u32) -> [RGB8; N] {
+        let mut pixels = [BLACK; N];
+        effect.render(t, &mut pixels);
+        pixels
+    }
+
+    #[test]
+    fn render_matches_pixel() {
+        let effects = [
+            Effect::Solid(RED),
+            Effect::Rainbow,
+            Effect::TheaterChase(RED),
+            Effect::Twinkle(RED),
+            Effect::Fire,
+            Effect::Comet(RED),
+            Effect::Gradient(RED, BLUE),
+            Effect::Breathing(RED),
+        ];
+        for effect in effects {
+            for t in [0, 1234, 99_999] {
+                let pixels: [RGB8; 12] = effect_frame(effect, t);
+                for (i, &pixel) in pixels.iter().enumerate() {
+                    assert_eq!(pixel, effect.pixel(i, 12, t), "{effect:?} at {t}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solid() {
+        assert_eq!(effect_frame::<3>(Effect::Solid(RED), 500), [RED; 3]);
+    }
+
+    #[test]
+    fn rainbow_spreads_the_wheel_and_turns() {
+        let effect = Effect::Rainbow;
+        assert_eq!(effect.pixel(0, 10, 0), wheel(0));
+        assert_eq!(effect.pixel(5, 10, 0), wheel(128));
+        assert_eq!(effect.pixel(0, 10, 100), wheel(10));
+    }
+
+    #[test]
+    fn theater_chase_marches_every_third() {
+        let effect = Effect::TheaterChase(RED);
+        assert_eq!(effect_frame::<6>(effect, 0), [RED, BLACK, BLACK, RED, BLACK, BLACK]);
+        assert_eq!(effect_frame::<6>(effect, 100), [BLACK, BLACK, RED, BLACK, BLACK, RED]);
+    }
+
+    #[test]
+    fn comet_has_a_fading_tail() {
+        let effect = Effect::Comet(RED);
+        let frame: [RGB8; 10] = effect_frame(effect, 0);
+        assert_eq!(frame[0], RED);
+        assert_eq!(frame[1..], [BLACK; 9]);
+
+        // Two pixels on, with the tail behind it.
+        let frame: [RGB8; 10] = effect_frame(effect, 80);
+        assert_eq!(frame[..3], [scale(RED, 85), scale(RED, 170), RED]);
+        assert_eq!(frame[3..], [BLACK; 7]);
+    }
+
+    #[test]
+    fn gradient_goes_there_and_back() {
+        let effect = Effect::Gradient(RED, BLUE);
+        assert_eq!(effect.pixel(0, 4, 0), RED);
+        assert_eq!(effect.pixel(2, 4, 0), BLUE);
+        // Scrolled on by half a strip.
+        assert_eq!(effect.pixel(0, 4, 5120), BLUE);
+        assert_eq!(effect.pixel(2, 4, 5120), RED);
+    }
+
+    #[test]
+    fn breathing_is_dark_then_bright() {
+        let effect = Effect::Breathing(RED);
+        assert_eq!(effect.pixel(0, 1, 0), BLACK);
+        assert_eq!(effect.pixel(0, 1, 2000), RED);
+        assert_eq!(effect.pixel(0, 1, 4000), BLACK);
+        assert!(effect.pixel(0, 1, 1000).r < 128);
+    }
+
+    #[test]
+    fn twinkle_is_the_same_every_run() {
+        let effect = Effect::Twinkle(WHITE);
+        let frame: [RGB8; 32] = effect_frame(effect, 5_000);
+        assert_eq!(frame, effect_frame(effect, 5_000));
+        // Only ever the one colour, dimmed.
+        assert!(frame.iter().all(|pixel| pixel.r == pixel.g && pixel.g == pixel.b));
+    }
+
+    #[test]
+    fn fire_is_hottest_at_the_start() {
+        for t in [0, 40, 1_000, 12_345] {
+            let frame: [RGB8; 10] = effect_frame(Effect::Fire, t);
+            assert_eq!(frame[0].r, 255, "{t}");
+            assert_eq!(frame[9].g, 0, "{t}");
+        }
+    }
+
+    #[test]
+    fn with_color_keeps_the_effect() {
+        assert_eq!(Effect::Comet(RED).with_color(BLUE), Effect::Comet(BLUE));
+        assert_eq!(Effect::Gradient(RED, WHITE).with_color(BLUE), Effect::Gradient(BLUE, WHITE));
+        assert_eq!(Effect::Fire.with_color(BLUE), Effect::Fire);
+    }
+
+    #[test]
+    fn crossfades_over_fade_ms() {
+        let mut animator = Animator::new(Effect::Solid(RED));
+        assert_eq!(frame::<2>(&mut animator, 1_000), [RED; 2]);
+
+        animator.set_effect(Effect::Solid(BLUE));
+        assert_eq!(frame::<2>(&mut animator, 1_000), [RED; 2]);
+        assert_eq!(frame::<2>(&mut animator, 1_250), [RGB8::new(128, 0, 127); 2]);
+        assert_eq!(frame::<2>(&mut animator, 1_500), [BLUE; 2]);
+
+        // Once it's over it stays over.
+        animator.set_fade(2_000);
+        assert_eq!(frame::<2>(&mut animator, 1_600), [BLUE; 2]);
+    }
+
+    #[test]
+    fn no_fade_cuts_straight_across() {
+        let mut animator = Animator::new(Effect::Solid(RED));
+        animator.set_fade(0);
+        frame::<1>(&mut animator, 0);
+        animator.set_effect(Effect::Solid(BLUE));
+        assert_eq!(frame::<1>(&mut animator, 0), [BLUE]);
+    }
+
+    #[test]
+    fn replace_effect_doesnt_fade() {
+        let mut animator = Animator::new(Effect::Solid(RED));
+        frame::<1>(&mut animator, 0);
+        animator.replace_effect(Effect::Solid(BLUE));
+        assert_eq!(animator.effect(), Effect::Solid(BLUE));
+        assert_eq!(frame::<1>(&mut animator, 10), [BLUE]);
+    }
+
+    #[test]
+    fn set_speed_doesnt_jump() {
+        let mut animator = Animator::new(Effect::Rainbow);
+        frame::<8>(&mut animator, 0);
+        let at_1s = frame::<8>(&mut animator, 1_000);
+        assert_eq!(at_1s, effect_frame(Effect::Rainbow, 1_000));
+
+        animator.set_speed(200);
+        assert_eq!(frame::<8>(&mut animator, 1_000), at_1s);
+        assert_eq!(frame::<8>(&mut animator, 1_500), effect_frame(Effect::Rainbow, 2_000));
+
+        animator.set_speed(50);
+        assert_eq!(frame::<8>(&mut animator, 2_500), effect_frame(Effect::Rainbow, 2_500));
+    }
+
+    #[test]
+    fn speed_zero_freezes() {
+        let mut animator = Animator::new(Effect::Rainbow);
+        frame::<8>(&mut animator, 0);
+        let at_1s = frame::<8>(&mut animator, 1_000);
+        animator.set_speed(0);
+        assert_eq!(frame::<8>(&mut animator, 5_000), at_1s);
+        assert_eq!(frame::<8>(&mut animator, 60_000), at_1s);
+
+        // And picks up from there.
+        animator.set_speed(100);
+        assert_eq!(frame::<8>(&mut animator, 60_500), effect_frame(Effect::Rainbow, 1_500));
+    }
+
+    #[test]
+    fn brightness_scales_every_pixel() {
+        let mut animator = Animator::new(Effect::Solid(WHITE));
+        animator.set_brightness(128);
+        assert_eq!(frame::<3>(&mut animator, 0), [RGB8::new(128, 128, 128); 3]);
+        animator.set_brightness(0);
+        assert_eq!(frame::<3>(&mut animator, 0), [BLACK; 3]);
+    }
+}
@@ -0,0 +1,327 @@
+// Read-only FAT12 disk image generated on the fly.
+//
+// The image holds a volume label and a single file in the root directory.
+// Nothing is stored, every sector is computed from the file size when it is
+// read, and file contents come from a callback, so the disk costs no RAM
+// beyond one sector buffer.
+//
+// Layout (512 byte sectors, one sector per cluster):
+//
+//   0        boot sector
+//   1..7     FAT 1
+//   7..13    FAT 2
+//   13       root directory (16 entries)
+//   14..     data, the file occupies consecutive clusters from 2
+
+pub const SECTOR_SIZE: usize = 512;
+pub const TOTAL_SECTORS: u32 = 2048;
+
+const RESERVED_SECTORS: u32 = 1;
+const NUM_FATS: u32 = 2;
+const FAT_SECTORS: u32 = 6;
+const ROOT_ENTRIES: u32 = 16;
+const ROOT_SECTORS: u32 = ROOT_ENTRIES * 32 / SECTOR_SIZE as u32;
+const FAT_START: u32 = RESERVED_SECTORS;
+const ROOT_START: u32 = FAT_START + NUM_FATS * FAT_SECTORS;
+const DATA_START: u32 = ROOT_START + ROOT_SECTORS;
+const CLUSTER_COUNT: u32 = TOTAL_SECTORS - DATA_START;
+
+pub const MAX_FILE_SIZE: u32 = CLUSTER_COUNT * SECTOR_SIZE as u32;
+
+// 2023-12-01 00:00, directory entries need some timestamp.
+const FAT_DATE: u16 = ((2023 - 1980) << 9) | (12 << 5) | 1;
+const FAT_TIME: u16 = 0;
+
+const VOLUME_ID: u32 = 0x2023_1201;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fat12Image {
+    label: [u8; 11],
+    name: [u8; 11],
+    size: u32,
+}
+
+impl Fat12Image {
+    /// `file_name` is an 8.3 name like "DATA.CSV", it is upper cased and
+    /// truncated to fit. Files larger than `MAX_FILE_SIZE` are cut short.
+    pub fn new(label: &str, file_name: &str, size: u32) -> Self {
+        let mut label_bytes = [b' '; 11];
+        copy_upper(&mut label_bytes, label);
+
+        let mut name = [b' '; 11];
+        let (base, ext) = file_name.rsplit_once('.').unwrap_or((file_name, ""));
+        copy_upper(&mut name[..8], base);
+        copy_upper(&mut name[8..], ext);
+
+        Self {
+            label: label_bytes,
+            name,
+            size: size.min(MAX_FILE_SIZE),
+        }
+    }
+
+    pub fn file_size(&self) -> u32 {
+        self.size
+    }
+
+    fn file_clusters(&self) -> u32 {
+        self.size.div_ceil(SECTOR_SIZE as u32)
+    }
+
+    /// Fills `buf` with sector `lba`. `read_file` is called with a byte
+    /// offset into the file and a buffer to fill for data sectors.
+    pub fn read_sector(&self, lba: u32, buf: &mut [u8; SECTOR_SIZE], mut read_file: impl FnMut(u32, &mut [u8])) {
+        buf.fill(0);
+
+        if lba < FAT_START {
+            self.boot_sector(buf);
+        } else if lba < ROOT_START {
+            let fat_sector = (lba - FAT_START) % FAT_SECTORS;
+            self.fat_sector(fat_sector, buf);
+        } else if lba < DATA_START {
+            self.root_dir(buf);
+        } else if lba < TOTAL_SECTORS {
+            let offset = (lba - DATA_START) * SECTOR_SIZE as u32;
+            if offset < self.size {
+                let len = (self.size - offset).min(SECTOR_SIZE as u32) as usize;
+                read_file(offset, &mut buf[..len]);
+            }
+        }
+    }
+
+    fn boot_sector(&self, buf: &mut [u8; SECTOR_SIZE]) {
+        buf[0..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+        buf[3..11].copy_from_slice(b"MSWIN4.1");
+        buf[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+        buf[13] = 1; // sectors per cluster
+        buf[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+        buf[16] = NUM_FATS as u8;
+        buf[17..19].copy_from_slice(&(ROOT_ENTRIES as u16).to_le_bytes());
+        buf[19..21].copy_from_slice(&(TOTAL_SECTORS as u16).to_le_bytes());
+        buf[21] = 0xF8; // fixed media
+        buf[22..24].copy_from_slice(&(FAT_SECTORS as u16).to_le_bytes());
+        buf[24..26].copy_from_slice(&32u16.to_le_bytes()); // sectors per track
+        buf[26..28].copy_from_slice(&2u16.to_le_bytes()); // heads
+        buf[36] = 0x80; // drive number
+        buf[38] = 0x29; // extended boot signature
+        buf[39..43].copy_from_slice(&VOLUME_ID.to_le_bytes());
+        buf[43..54].copy_from_slice(&self.label);
+        buf[54..62].copy_from_slice(b"FAT12   ");
+        buf[510] = 0x55;
+        buf[511] = 0xAA;
+    }
+
+    fn fat_entry(&self, cluster: u32) -> u16 {
+        let clusters = self.file_clusters();
+        match cluster {
+            0 => 0xFF8,
+            1 => 0xFFF,
+            c if c >= 2 && c < 2 + clusters => {
+                if c == 1 + clusters {
+                    0xFFF
+                } else {
+                    (c + 1) as u16
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    fn fat_sector(&self, sector: u32, buf: &mut [u8; SECTOR_SIZE]) {
+        // FAT12 packs two 12 bit entries into every three bytes.
+        let start = sector * SECTOR_SIZE as u32;
+        for (i, byte) in buf.iter_mut().enumerate() {
+            let offset = start + i as u32;
+            let pair = offset / 3;
+            let e0 = self.fat_entry(pair * 2);
+            let e1 = self.fat_entry(pair * 2 + 1);
+            *byte = match offset % 3 {
+                0 => e0 as u8,
+                1 => ((e0 >> 8) as u8 & 0x0F) | ((e1 as u8 & 0x0F) << 4),
+                _ => (e1 >> 4) as u8,
+            };
+        }
+    }
+
+    fn root_dir(&self, buf: &mut [u8; SECTOR_SIZE]) {
+        let (label, file) = buf.split_at_mut(32);
+
+        label[0..11].copy_from_slice(&self.label);
+        label[11] = 0x08; // volume label
+        write_timestamps(label);
+
+        file[0..11].copy_from_slice(&self.name);
+        file[11] = 0x01; // read only
+        write_timestamps(file);
+        let first_cluster: u16 = if self.size > 0 { 2 } else { 0 };
+        file[26..28].copy_from_slice(&first_cluster.to_le_bytes());
+        file[28..32].copy_from_slice(&self.size.to_le_bytes());
+    }
+}
+
+fn write_timestamps(entry: &mut [u8]) {
+    // creation, last access and modification all get the same date
+    entry[14..16].copy_from_slice(&FAT_TIME.to_le_bytes());
+    entry[16..18].copy_from_slice(&FAT_DATE.to_le_bytes());
+    entry[18..20].copy_from_slice(&FAT_DATE.to_le_bytes());
+    entry[22..24].copy_from_slice(&FAT_TIME.to_le_bytes());
+    entry[24..26].copy_from_slice(&FAT_DATE.to_le_bytes());
+}
+
+fn copy_upper(dst: &mut [u8], src: &str) {
+    for (d, s) in dst.iter_mut().zip(src.bytes()) {
+        *d = s.to_ascii_uppercase();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    fn sector(image: &Fat12Image, lba: u32) -> [u8; SECTOR_SIZE] {
+        let mut buf = [0xAA; SECTOR_SIZE];
+        image.read_sector(lba, &mut buf, |offset, data| {
+            for (i, byte) in data.iter_mut().enumerate() {
+                *byte = file_byte(offset + i as u32);
+            }
+        });
+        buf
+    }
+
+    fn file_byte(offset: u32) -> u8 {
+        (offset % 251) as u8
+    }
+
+    fn u16_at(buf: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([buf[at], buf[at + 1]])
+    }
+
+    fn u32_at(buf: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+    }
+
+    // Both FATs read back as bytes, as a host would.
+    fn fats(image: &Fat12Image) -> (Vec<u8>, Vec<u8>) {
+        let read = |first: u32| (first..first + FAT_SECTORS).flat_map(|lba| sector(image, lba)).collect::<Vec<u8>>();
+        (read(FAT_START), read(FAT_START + FAT_SECTORS))
+    }
+
+    fn fat_entry(fat: &[u8], cluster: usize) -> u16 {
+        let at = cluster * 3 / 2;
+        let pair = u16_at(fat, at);
+        if cluster % 2 == 0 {
+            pair & 0x0FFF
+        } else {
+            pair >> 4
+        }
+    }
+
+    // Reads the file back the way a host would, from the root entry and
+    // the FAT chain.
+    fn read_back(image: &Fat12Image) -> (std::string::String, Vec<u8>) {
+        let root = sector(image, ROOT_START);
+        let entry = &root[32..64];
+        let name = std::string::String::from_utf8(entry[0..11].to_vec()).unwrap();
+        let size = u32_at(entry, 28) as usize;
+        let (fat, _) = fats(image);
+
+        let mut data = Vec::new();
+        let mut cluster = u16_at(entry, 26);
+        while (2..0xFF8).contains(&cluster) {
+            data.extend_from_slice(&sector(image, DATA_START + cluster as u32 - 2));
+            cluster = fat_entry(&fat, cluster as usize);
+        }
+        assert!(data.len() >= size, "chain shorter than the file");
+        data.truncate(size);
+        (name, data)
+    }
+
+    #[test]
+    fn boot_sector() {
+        let image = Fat12Image::new("codemas", "data.csv", 1300);
+        let boot = sector(&image, 0);
+        assert_eq!(&boot[0..3], &[0xEB, 0x3C, 0x90]);
+        assert_eq!(u16_at(&boot, 11), 512);
+        assert_eq!(boot[13], 1);
+        assert_eq!(u16_at(&boot, 14), RESERVED_SECTORS as u16);
+        assert_eq!(boot[16], 2);
+        assert_eq!(u16_at(&boot, 17), ROOT_ENTRIES as u16);
+        assert_eq!(u16_at(&boot, 19), TOTAL_SECTORS as u16);
+        assert_eq!(boot[21], 0xF8);
+        assert_eq!(u16_at(&boot, 22), FAT_SECTORS as u16);
+        assert_eq!(&boot[43..54], b"CODEMAS    ");
+        assert_eq!(&boot[54..62], b"FAT12   ");
+        assert_eq!(&boot[510..512], &[0x55, 0xAA]);
+
+        // What a host works the layout out from.
+        let root_start = u16_at(&boot, 14) as u32 + boot[16] as u32 * u16_at(&boot, 22) as u32;
+        assert_eq!(root_start, ROOT_START);
+        let data_start = root_start + u16_at(&boot, 17) as u32 * 32 / 512;
+        assert_eq!(data_start, DATA_START);
+        // Under 4085 clusters is what makes it FAT12.
+        assert!(TOTAL_SECTORS - data_start < 4085);
+    }
+
+    #[test]
+    fn root_directory() {
+        let image = Fat12Image::new("codemas", "data.csv", 1300);
+        let root = sector(&image, ROOT_START);
+        assert_eq!(&root[0..11], b"CODEMAS    ");
+        assert_eq!(root[11], 0x08);
+        assert_eq!(&root[32..43], b"DATA    CSV");
+        assert_eq!(root[32 + 11], 0x01);
+        assert_eq!(u16_at(&root, 32 + 26), 2);
+        assert_eq!(u32_at(&root, 32 + 28), 1300);
+        // No more entries.
+        assert_eq!(root[64], 0);
+    }
+
+    #[test]
+    fn fat_chain() {
+        let image = Fat12Image::new("codemas", "data.csv", 1300);
+        let (fat, copy) = fats(&image);
+        assert_eq!(fat, copy);
+        assert_eq!(fat_entry(&fat, 0), 0xFF8);
+        assert_eq!(fat_entry(&fat, 1), 0xFFF);
+        assert_eq!(fat_entry(&fat, 2), 3);
+        assert_eq!(fat_entry(&fat, 3), 4);
+        assert_eq!(fat_entry(&fat, 4), 0xFFF);
+        assert_eq!(fat_entry(&fat, 5), 0);
+    }
+
+    #[test]
+    fn file_reads_back_through_the_fat() {
+        // Sizes either side of a sector, and one whose chain crosses from
+        // the first FAT sector into the second.
+        for size in [1, 511, 512, 513, 1300, 400 * 512 + 7] {
+            let image = Fat12Image::new("codemas", "data.csv", size);
+            let (name, data) = read_back(&image);
+            assert_eq!(name, "DATA    CSV");
+            assert_eq!(data.len(), size as usize);
+            assert!(data.iter().enumerate().all(|(i, &b)| b == file_byte(i as u32)), "size {size}");
+        }
+    }
+
+    #[test]
+    fn empty_file() {
+        let image = Fat12Image::new("codemas", "data.csv", 0);
+        let root = sector(&image, ROOT_START);
+        assert_eq!(u16_at(&root, 32 + 26), 0);
+        assert_eq!(u32_at(&root, 32 + 28), 0);
+        let (fat, _) = fats(&image);
+        assert!(fat[3..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn sectors_past_the_file_are_blank() {
+        let image = Fat12Image::new("codemas", "data.csv", 600);
+        let data = sector(&image, DATA_START + 1);
+        assert!(data[..88].iter().enumerate().all(|(i, &b)| b == file_byte(512 + i as u32)));
+        assert!(data[88..].iter().all(|&b| b == 0));
+        assert_eq!(sector(&image, DATA_START + 2), [0; SECTOR_SIZE]);
+        assert_eq!(sector(&image, TOTAL_SECTORS - 1), [0; SECTOR_SIZE]);
+    }
+}
@@ -0,0 +1,313 @@
+// In-memory stand-in for the OLED, for checking screens without hardware.
+//
+// `FrameBuffer` implements the same `Flush` draw target as the real panel,
+// so anything drawn through `Screen` can be drawn here instead and then
+// compared against a golden image or dumped to a PNG.
+//
+// Golden images are ASCII art, one line per row with `#` for a lit pixel
+// and `.` for a dark one. That's exactly what the `Display` impl prints, so
+// a new golden image is just the output of `format!("{}", fb)`.
+
+use core::convert::Infallible;
+use core::fmt;
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+
+use crate::display::Flush;
+
+pub type FrameBuffer128x64 = FrameBuffer<128, 64>;
+pub type FrameBuffer128x32 = FrameBuffer<128, 32>;
+
+pub struct FrameBuffer<const W: usize, const H: usize> {
+    pixels: [[bool; W]; H],
+    flushes: u32,
+}
+
+impl<const W: usize, const H: usize> FrameBuffer<W, H> {
+    pub const fn new() -> Self {
+        Self {
+            pixels: [[false; W]; H],
+            flushes: 0,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels.get(y).and_then(|row| row.get(x)).copied().unwrap_or(false)
+    }
+
+    /// How many times the screen has been flushed, handy for checking a
+    /// redraw didn't happen more often than it should.
+    pub fn flushes(&self) -> u32 {
+        self.flushes
+    }
+
+    /// Compares against a golden image and returns the (x, y) of the first
+    /// pixel that differs. Trailing whitespace and blank lines around the
+    /// image are ignored; a golden image of the wrong size differs at the
+    /// first missing or extra pixel.
+    pub fn diff(&self, golden: &str) -> Option<(usize, usize)> {
+        let mut rows = golden.trim().lines().map(str::trim_end);
+        for y in 0..H {
+            let row = rows.next().unwrap_or("");
+            let mut cells = row.chars();
+            for x in 0..W {
+                let expected = match cells.next() {
+                    Some('#') => true,
+                    Some('.') => false,
+                    _ => return Some((x, y)),
+                };
+                if self.pixels[y][x] != expected {
+                    return Some((x, y));
+                }
+            }
+            if cells.next().is_some() {
+                return Some((W, y));
+            }
+        }
+        if rows.next().is_some() {
+            return Some((0, H));
+        }
+        None
+    }
+
+    /// Fails the test if the screen doesn't match `golden`, printing what
+    /// it does show so a deliberate change can be pasted in as the new
+    /// golden image.
+    #[cfg(test)]
+    pub(crate) fn assert_golden(&self, golden: &str) {
+        if let Some((x, y)) = self.diff(golden) {
+            panic!("differs from the golden image at ({x}, {y}), the screen is now:\n{self}");
+        }
+    }
+
+    /// Encodes the screen as a 1 bit greyscale PNG, lit pixels white.
+    pub fn write_png(&self, mut out: impl FnMut(&[u8])) {
+        const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+        let row_bytes = W.div_ceil(8);
+        // every row starts with a filter type byte
+        let raw_len = H * (1 + row_bytes);
+
+        out(&SIGNATURE);
+
+        let mut ihdr = [0u8; 13];
+        ihdr[0..4].copy_from_slice(&(W as u32).to_be_bytes());
+        ihdr[4..8].copy_from_slice(&(H as u32).to_be_bytes());
+        ihdr[8] = 1; // bit depth
+        ihdr[9] = 0; // greyscale
+        let mut chunk = ChunkWriter::start(&mut out, b"IHDR", ihdr.len());
+        chunk.write(&ihdr);
+        chunk.finish();
+
+        // Uncompressed deflate: zlib header, stored blocks of at most 65535
+        // bytes, then the adler32 of the raw data.
+        let blocks = raw_len.div_ceil(0xFFFF).max(1);
+        let mut chunk = ChunkWriter::start(&mut out, b"IDAT", 2 + blocks * 5 + raw_len + 4);
+        chunk.write(&[0x78, 0x01]);
+
+        let mut stored = StoredBlocks { chunk, remaining: raw_len, block_left: 0, adler: Adler32::new() };
+        for row in &self.pixels {
+            stored.write(0); // no filter
+            for byte_x in 0..row_bytes {
+                let mut byte = 0u8;
+                for bit in 0..8 {
+                    if row.get(byte_x * 8 + bit).copied().unwrap_or(false) {
+                        byte |= 0x80 >> bit;
+                    }
+                }
+                stored.write(byte);
+            }
+        }
+
+        let adler = stored.adler.finish();
+        let mut chunk = stored.chunk;
+        chunk.write(&adler.to_be_bytes());
+        chunk.finish();
+
+        ChunkWriter::start(&mut out, b"IEND", 0).finish();
+    }
+}
+
+impl<const W: usize, const H: usize> Default for FrameBuffer<W, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize, const H: usize> fmt::Display for FrameBuffer<W, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in &self.pixels {
+            for &on in row {
+                f.write_str(if on { "#" } else { "." })?;
+            }
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl<const W: usize, const H: usize> OriginDimensions for FrameBuffer<W, H> {
+    fn size(&self) -> Size {
+        Size::new(W as u32, H as u32)
+    }
+}
+
+impl<const W: usize, const H: usize> DrawTarget for FrameBuffer<W, H> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) {
+                if x < W && y < H {
+                    self.pixels[y][x] = color.is_on();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<const W: usize, const H: usize> Flush for FrameBuffer<W, H> {
+    fn flush(&mut self) -> Result<(), Infallible> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+// Writes a PNG chunk whose length is known up front, keeping a running CRC.
+struct ChunkWriter<'a, F: FnMut(&[u8])> {
+    out: &'a mut F,
+    crc: u32,
+}
+
+impl<'a, F: FnMut(&[u8])> ChunkWriter<'a, F> {
+    fn start(out: &'a mut F, kind: &[u8; 4], len: usize) -> Self {
+        out(&(len as u32).to_be_bytes());
+        let mut chunk = Self { out, crc: 0xFFFF_FFFF };
+        chunk.write(kind);
+        chunk
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.crc ^= byte as u32;
+            for _ in 0..8 {
+                self.crc = if self.crc & 1 != 0 {
+                    (self.crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    self.crc >> 1
+                };
+            }
+        }
+        (self.out)(data);
+    }
+
+    fn finish(self) {
+        (self.out)(&(!self.crc).to_be_bytes());
+    }
+}
+
+// Splits the raw image data into deflate stored blocks as it's written.
+struct StoredBlocks<'a, F: FnMut(&[u8])> {
+    chunk: ChunkWriter<'a, F>,
+    remaining: usize,
+    block_left: usize,
+    adler: Adler32,
+}
+
+impl<F: FnMut(&[u8])> StoredBlocks<'_, F> {
+    fn write(&mut self, byte: u8) {
+        if self.block_left == 0 {
+            self.block_left = self.remaining.min(0xFFFF);
+            let last = (self.block_left == self.remaining) as u8;
+            let len = (self.block_left as u16).to_le_bytes();
+            let nlen = (!(self.block_left as u16)).to_le_bytes();
+            self.chunk.write(&[last, len[0], len[1], nlen[0], nlen[1]]);
+        }
+        self.chunk.write(&[byte]);
+        self.adler.update(&[byte]);
+        self.block_left -= 1;
+        self.remaining -= 1;
+    }
+}
+
+struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.a = (self.a + byte as u32) % 65521;
+            self.b = (self.b + self.a) % 65521;
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+    use std::format;
+
+    fn small() -> FrameBuffer<4, 3> {
+        let mut fb = FrameBuffer::new();
+        Rectangle::new(Point::new(1, 1), Size::new(2, 1))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(&mut fb)
+            .unwrap();
+        fb
+    }
+
+    #[test]
+    fn display_is_a_golden_image() {
+        let fb = small();
+        assert_eq!(format!("{fb}"), "....\n.##.\n....\n");
+        assert_eq!(fb.diff(&format!("{fb}")), None);
+    }
+
+    #[test]
+    fn diff_finds_the_first_difference() {
+        let fb = small();
+        assert_eq!(fb.diff("....\n.#..\n...."), Some((2, 1)));
+        assert_eq!(fb.diff("\n....\n.##.   \n....\n\n"), None);
+    }
+
+    #[test]
+    fn diff_catches_the_wrong_size() {
+        let fb = small();
+        assert_eq!(fb.diff("...\n.##\n..."), Some((3, 0)));
+        assert_eq!(fb.diff(".....\n.##..\n....."), Some((4, 0)));
+        assert_eq!(fb.diff("....\n.##."), Some((0, 2)));
+        assert_eq!(fb.diff("....\n.##.\n....\n...."), Some((0, 3)));
+    }
+
+    #[test]
+    fn png_layout() {
+        let mut png = std::vec::Vec::new();
+        small().write_png(|bytes| png.extend_from_slice(bytes));
+        assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 4, 0, 0, 0, 3]);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        // Rows of a filter byte then the pixels, in a single stored block.
+        let idat = 8 + 25 + 8;
+        assert_eq!(&png[idat - 4..idat], b"IDAT");
+        assert_eq!(&png[idat + 2 + 5..idat + 2 + 5 + 6], &[0, 0x00, 0, 0x60, 0, 0x00]);
+    }
+}
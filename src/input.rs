@@ -0,0 +1,250 @@
+// GPIO inputs by edge interrupt instead of polling.
+//
+// `EdgeInputs` lives in the IO_IRQ_BANK0 handler. It has both edge
+// interrupts enabled on each of its pins, and each time the handler calls
+// `service` it timestamps whatever edges have happened and pushes them onto
+// a `heapless` single producer single consumer queue. The main loop takes
+// them off the other end with an `EdgeReceiver`, without either side
+// having to lock out the other, and can sleep in `wait` until there's
+// something to do.
+//
+// If the queue is full the edge is dropped. The next one that does fit
+// says how many went missing before it, so the main loop knows its idea of
+// the pin levels might be out of date.
+
+use embedded_hal::digital::v2::InputPin;
+use heapless::spsc::{Consumer, Producer, Queue};
+use heapless::Vec;
+use rp_pico::hal::gpio::{DynPinId, DynPullType, FunctionSioInput, Interrupt, Pin, PinId, PullType};
+use rp_pico::hal::Timer;
+
+/// Where the edges wait for the main loop, holding `N - 1` of them.
+pub type EventQueue<const N: usize> = Queue<InputEvent, N>;
+
+/// A pin as `EdgeInputs` holds it, whichever pin it was.
+pub type EdgePin = Pin<DynPinId, FunctionSioInput, DynPullType>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+impl Edge {
+    /// The level the pin is at afterwards.
+    pub fn is_high(self) -> bool {
+        self == Edge::Rising
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// Which input, in the order they were added.
+    pub input: usize,
+    pub edge: Edge,
+    /// Timer ticks, µs since boot.
+    pub at_us: u64,
+    /// Edges dropped since the last event because the queue was full.
+    pub missed: u32,
+}
+
+/// The interrupt's end of an `EventQueue`.
+pub struct EdgeSender<'a, const N: usize> {
+    producer: Producer<'a, InputEvent, N>,
+    missed: u32,
+}
+
+impl<'a, const N: usize> EdgeSender<'a, N> {
+    pub fn new(producer: Producer<'a, InputEvent, N>) -> Self {
+        Self { producer, missed: 0 }
+    }
+
+    /// Queues an edge, returning false if there wasn't room for it.
+    pub fn send(&mut self, input: usize, edge: Edge, at_us: u64) -> bool {
+        let event = InputEvent { input, edge, at_us, missed: self.missed };
+        if self.producer.enqueue(event).is_err() {
+            self.missed = self.missed.saturating_add(1);
+            return false;
+        }
+        self.missed = 0;
+        true
+    }
+}
+
+/// The main loop's end of an `EventQueue`.
+pub struct EdgeReceiver<'a, const N: usize> {
+    consumer: Consumer<'a, InputEvent, N>,
+}
+
+impl<'a, const N: usize> EdgeReceiver<'a, N> {
+    pub fn new(consumer: Consumer<'a, InputEvent, N>) -> Self {
+        Self { consumer }
+    }
+
+    /// The oldest event not yet taken, if any.
+    pub fn next_event(&mut self) -> Option<InputEvent> {
+        self.consumer.dequeue()
+    }
+
+    pub fn pending(&self) -> usize {
+        self.consumer.len()
+    }
+
+    /// Sleeps until there's an event and takes it. Any interrupt wakes the
+    /// core, so this can also return to run something else's handler
+    /// first, but never sleeps through an event that's already queued.
+    pub fn wait(&mut self) -> InputEvent {
+        loop {
+            if let Some(event) = self.wait_until(|| false) {
+                return event;
+            }
+        }
+    }
+
+    /// Like `wait`, but gives up with `None` once `timed_out` says so.
+    /// That's only checked when something wakes the core, so there needs
+    /// to be an interrupt at the deadline too, a timer alarm say.
+    pub fn wait_until(&mut self, mut timed_out: impl FnMut() -> bool) -> Option<InputEvent> {
+        loop {
+            if let Some(event) = self.consumer.dequeue() {
+                return Some(event);
+            }
+            // With interrupts masked an edge or the alarm can't sneak in
+            // between looking and sleeping, and a pending one still ends
+            // the wfi.
+            let expired = cortex_m::interrupt::free(|_| {
+                let expired = timed_out();
+                if !expired && !self.consumer.ready() {
+                    cortex_m::asm::wfi();
+                }
+                expired
+            });
+            if expired {
+                return None;
+            }
+        }
+    }
+}
+
+/// Splits a queue into its two ends.
+pub fn split<const N: usize>(queue: &mut EventQueue<N>) -> (EdgeSender<'_, N>, EdgeReceiver<'_, N>) {
+    let (producer, consumer) = queue.split();
+    (EdgeSender::new(producer), EdgeReceiver::new(consumer))
+}
+
+/// Up to `P` pins watched for edges, feeding a queue of `N`.
+pub struct EdgeInputs<'a, const P: usize, const N: usize> {
+    pins: Vec<EdgePin, P>,
+    timer: Timer,
+    sender: EdgeSender<'a, N>,
+}
+
+impl<'a, const P: usize, const N: usize> EdgeInputs<'a, P, N> {
+    pub fn new(timer: Timer, sender: EdgeSender<'a, N>) -> Self {
+        Self { pins: Vec::new(), timer, sender }
+    }
+
+    /// Starts watching a pin and returns the number its events will have.
+    /// The IO_IRQ_BANK0 interrupt still needs unmasking. Panics if there
+    /// are already `P` pins.
+    pub fn add<I: PinId, M: PullType>(&mut self, pin: Pin<I, FunctionSioInput, M>) -> usize {
+        let pin = pin.into_dyn_pin().into_pull_type::<DynPullType>();
+        for interrupt in [Interrupt::EdgeLow, Interrupt::EdgeHigh] {
+            pin.set_interrupt_enabled(interrupt, true);
+        }
+        if self.pins.push(pin).is_err() {
+            panic!("too many inputs");
+        }
+        self.pins.len() - 1
+    }
+
+    /// The level an input is at now.
+    pub fn is_high(&self, input: usize) -> Option<bool> {
+        self.pins.get(input).map(|pin| pin.is_high().unwrap())
+    }
+
+    /// Queues any edges on the pins and clears their interrupts. Call from
+    /// IO_IRQ_BANK0.
+    pub fn service(&mut self) {
+        let at_us = self.timer.get_counter().ticks();
+        for (input, pin) in self.pins.iter_mut().enumerate() {
+            let fell = pin.interrupt_status(Interrupt::EdgeLow);
+            let rose = pin.interrupt_status(Interrupt::EdgeHigh);
+            if fell {
+                pin.clear_interrupt(Interrupt::EdgeLow);
+            }
+            if rose {
+                pin.clear_interrupt(Interrupt::EdgeHigh);
+            }
+            // Both at once means a pulse shorter than the interrupt took to
+            // get here. Going by where the pin is now, it went away and
+            // came back.
+            let edges = match (fell, rose, pin.is_high().unwrap()) {
+                (true, true, true) => [Some(Edge::Falling), Some(Edge::Rising)],
+                (true, true, false) => [Some(Edge::Rising), Some(Edge::Falling)],
+                (true, false, _) => [Some(Edge::Falling), None],
+                (false, true, _) => [Some(Edge::Rising), None],
+                (false, false, _) => [None, None],
+            };
+            for edge in edges.into_iter().flatten() {
+                self.sender.send(input, edge, at_us);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_come_out_in_the_order_they_went_in() {
+        let mut queue: EventQueue<8> = EventQueue::new();
+        let (mut sender, mut receiver) = split(&mut queue);
+        assert!(sender.send(0, Edge::Rising, 10));
+        assert!(sender.send(1, Edge::Rising, 20));
+        assert!(sender.send(0, Edge::Falling, 30));
+        assert_eq!(receiver.pending(), 3);
+
+        let event = |input, edge, at_us| Some(InputEvent { input, edge, at_us, missed: 0 });
+        assert_eq!(receiver.next_event(), event(0, Edge::Rising, 10));
+        assert_eq!(receiver.next_event(), event(1, Edge::Rising, 20));
+        assert_eq!(receiver.next_event(), event(0, Edge::Falling, 30));
+        assert_eq!(receiver.next_event(), None);
+        assert_eq!(receiver.pending(), 0);
+    }
+
+    #[test]
+    fn queue_holds_one_less_than_its_size() {
+        let mut queue: EventQueue<4> = EventQueue::new();
+        let (mut sender, mut receiver) = split(&mut queue);
+        for at_us in 0..3 {
+            assert!(sender.send(0, Edge::Rising, at_us));
+        }
+        assert!(!sender.send(0, Edge::Falling, 3));
+        assert_eq!(receiver.pending(), 3);
+
+        // Taking one makes room for one more.
+        receiver.next_event();
+        assert!(sender.send(0, Edge::Falling, 4));
+        assert!(!sender.send(0, Edge::Rising, 5));
+    }
+
+    #[test]
+    fn missed_edges_are_counted_on_the_next_event() {
+        let mut queue: EventQueue<2> = EventQueue::new();
+        let (mut sender, mut receiver) = split(&mut queue);
+        assert!(sender.send(0, Edge::Rising, 0));
+        assert!(!sender.send(0, Edge::Falling, 1));
+        assert!(!sender.send(0, Edge::Rising, 2));
+
+        assert_eq!(receiver.next_event().map(|event| event.missed), Some(0));
+        assert!(sender.send(0, Edge::Falling, 3));
+        let event = receiver.next_event().unwrap();
+        assert_eq!((event.at_us, event.missed), (3, 2));
+
+        // And only that one.
+        assert!(sender.send(0, Edge::Rising, 4));
+        assert_eq!(receiver.next_event().map(|event| event.missed), Some(0));
+    }
+}
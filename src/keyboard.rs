@@ -0,0 +1,310 @@
+// USB HID keyboard reports and a US layout ASCII key map.
+//
+// The descriptor is the standard 8 byte boot keyboard report (modifiers,
+// reserved, six key slots) so the reports work without a custom driver on
+// any host.
+
+pub const REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xA1, 0x01, // Collection (Application)
+    0x05, 0x07, //   Usage Page (Key Codes)
+    0x19, 0xE0, //   Usage Minimum (224)
+    0x29, 0xE7, //   Usage Maximum (231)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x08, //   Report Count (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute) ; modifier byte
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x08, //   Report Size (8)
+    0x81, 0x01, //   Input (Constant) ; reserved byte
+    0x95, 0x05, //   Report Count (5)
+    0x75, 0x01, //   Report Size (1)
+    0x05, 0x08, //   Usage Page (LEDs)
+    0x19, 0x01, //   Usage Minimum (1)
+    0x29, 0x05, //   Usage Maximum (5)
+    0x91, 0x02, //   Output (Data, Variable, Absolute) ; LED report
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x03, //   Report Size (3)
+    0x91, 0x01, //   Output (Constant) ; LED report padding
+    0x95, 0x06, //   Report Count (6)
+    0x75, 0x08, //   Report Size (8)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x65, //   Logical Maximum (101)
+    0x05, 0x07, //   Usage Page (Key Codes)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0x65, //   Usage Maximum (101)
+    0x81, 0x00, //   Input (Data, Array) ; key slots
+    0xC0,       // End Collection
+];
+
+pub mod modifier {
+    pub const LEFT_CTRL: u8 = 0x01;
+    pub const LEFT_SHIFT: u8 = 0x02;
+    pub const LEFT_ALT: u8 = 0x04;
+    pub const LEFT_GUI: u8 = 0x08;
+    pub const RIGHT_CTRL: u8 = 0x10;
+    pub const RIGHT_SHIFT: u8 = 0x20;
+    pub const RIGHT_ALT: u8 = 0x40;
+    pub const RIGHT_GUI: u8 = 0x80;
+}
+
+// The letters, and a handful of usage IDs that don't map to a printable
+// character.
+pub mod key {
+    pub const A: u8 = 0x04;
+    pub const B: u8 = 0x05;
+    pub const C: u8 = 0x06;
+    pub const D: u8 = 0x07;
+    pub const E: u8 = 0x08;
+    pub const F: u8 = 0x09;
+    pub const G: u8 = 0x0A;
+    pub const H: u8 = 0x0B;
+    pub const I: u8 = 0x0C;
+    pub const J: u8 = 0x0D;
+    pub const K: u8 = 0x0E;
+    pub const L: u8 = 0x0F;
+    pub const M: u8 = 0x10;
+    pub const N: u8 = 0x11;
+    pub const O: u8 = 0x12;
+    pub const P: u8 = 0x13;
+    pub const Q: u8 = 0x14;
+    pub const R: u8 = 0x15;
+    pub const S: u8 = 0x16;
+    pub const T: u8 = 0x17;
+    pub const U: u8 = 0x18;
+    pub const V: u8 = 0x19;
+    pub const W: u8 = 0x1A;
+    pub const X: u8 = 0x1B;
+    pub const Y: u8 = 0x1C;
+    pub const Z: u8 = 0x1D;
+    pub const ENTER: u8 = 0x28;
+    pub const ESCAPE: u8 = 0x29;
+    pub const BACKSPACE: u8 = 0x2A;
+    pub const TAB: u8 = 0x2B;
+    pub const SPACE: u8 = 0x2C;
+    pub const F1: u8 = 0x3A;
+    pub const DELETE: u8 = 0x4C;
+    pub const RIGHT: u8 = 0x4F;
+    pub const LEFT: u8 = 0x50;
+    pub const DOWN: u8 = 0x51;
+    pub const UP: u8 = 0x52;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyboardReport {
+    pub modifier: u8,
+    pub keys: [u8; 6],
+}
+
+impl KeyboardReport {
+    pub const fn empty() -> Self {
+        Self { modifier: 0, keys: [0; 6] }
+    }
+
+    pub const fn key(modifier: u8, key: u8) -> Self {
+        Self { modifier, keys: [key, 0, 0, 0, 0, 0] }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let k = self.keys;
+        [self.modifier, 0, k[0], k[1], k[2], k[3], k[4], k[5]]
+    }
+}
+
+/// Maps a character to the modifier and usage ID that types it on a US
+/// layout.
+pub fn ascii_to_key(c: char) -> Option<(u8, u8)> {
+    const SHIFT: u8 = modifier::LEFT_SHIFT;
+
+    let key = match c {
+        'a'..='z' => (0, key::A + (c as u8 - b'a')),
+        'A'..='Z' => (SHIFT, key::A + (c as u8 - b'A')),
+        '1'..='9' => (0, 0x1E + (c as u8 - b'1')),
+        '0' => (0, 0x27),
+        '\n' => (0, key::ENTER),
+        '\t' => (0, key::TAB),
+        ' ' => (0, key::SPACE),
+        '!' => (SHIFT, 0x1E),
+        '@' => (SHIFT, 0x1F),
+        '#' => (SHIFT, 0x20),
+        '$' => (SHIFT, 0x21),
+        '%' => (SHIFT, 0x22),
+        '^' => (SHIFT, 0x23),
+        '&' => (SHIFT, 0x24),
+        '*' => (SHIFT, 0x25),
+        '(' => (SHIFT, 0x26),
+        ')' => (SHIFT, 0x27),
+        '-' => (0, 0x2D),
+        '_' => (SHIFT, 0x2D),
+        '=' => (0, 0x2E),
+        '+' => (SHIFT, 0x2E),
+        '[' => (0, 0x2F),
+        '{' => (SHIFT, 0x2F),
+        ']' => (0, 0x30),
+        '}' => (SHIFT, 0x30),
+        '\\' => (0, 0x31),
+        '|' => (SHIFT, 0x31),
+        ';' => (0, 0x33),
+        ':' => (SHIFT, 0x33),
+        '\'' => (0, 0x34),
+        '"' => (SHIFT, 0x34),
+        '`' => (0, 0x35),
+        '~' => (SHIFT, 0x35),
+        ',' => (0, 0x36),
+        '<' => (SHIFT, 0x36),
+        '.' => (0, 0x37),
+        '>' => (SHIFT, 0x37),
+        '/' => (0, 0x38),
+        '?' => (SHIFT, 0x38),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// What a macro button does when pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Macro {
+    /// Types out a string. Characters with no key mapping are skipped.
+    Text(&'static str),
+    /// Presses a single key with modifiers held, e.g. ctrl+alt+t.
+    Hotkey { modifier: u8, key: u8 },
+}
+
+impl Macro {
+    pub fn reports(&self) -> MacroReports {
+        MacroReports {
+            mac: *self,
+            pos: 0,
+            released: true,
+        }
+    }
+}
+
+/// The sequence of reports that plays a macro. Every key press is followed
+/// by a release so repeated characters register as separate presses.
+pub struct MacroReports {
+    mac: Macro,
+    pos: usize,
+    released: bool,
+}
+
+impl Iterator for MacroReports {
+    type Item = KeyboardReport;
+
+    fn next(&mut self) -> Option<KeyboardReport> {
+        if !self.released {
+            self.released = true;
+            return Some(KeyboardReport::empty());
+        }
+
+        match self.mac {
+            Macro::Text(text) => {
+                let rest = text.get(self.pos..)?;
+                let mut chars = rest.chars();
+                loop {
+                    let c = chars.next()?;
+                    self.pos += c.len_utf8();
+                    if let Some((modifier, key)) = ascii_to_key(c) {
+                        self.released = false;
+                        return Some(KeyboardReport::key(modifier, key));
+                    }
+                }
+            }
+            Macro::Hotkey { modifier, key } => {
+                if self.pos > 0 {
+                    return None;
+                }
+                self.pos = 1;
+                self.released = false;
+                Some(KeyboardReport::key(modifier, key))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    const SHIFT: u8 = modifier::LEFT_SHIFT;
+
+    #[test]
+    fn letters() {
+        assert_eq!(ascii_to_key('a'), Some((0, key::A)));
+        assert_eq!(ascii_to_key('t'), Some((0, key::T)));
+        assert_eq!(ascii_to_key('z'), Some((0, key::Z)));
+        assert_eq!(ascii_to_key('A'), Some((SHIFT, key::A)));
+        assert_eq!(ascii_to_key('Q'), Some((SHIFT, key::Q)));
+    }
+
+    #[test]
+    fn digits() {
+        assert_eq!(ascii_to_key('1'), Some((0, 0x1E)));
+        assert_eq!(ascii_to_key('9'), Some((0, 0x26)));
+        assert_eq!(ascii_to_key('0'), Some((0, 0x27)));
+    }
+
+    #[test]
+    fn shifted_symbols_share_keys_with_their_unshifted_ones() {
+        assert_eq!(ascii_to_key('!'), Some((SHIFT, 0x1E)));
+        assert_eq!(ascii_to_key(')'), Some((SHIFT, 0x27)));
+        for (plain, shifted) in [('-', '_'), ('=', '+'), ('[', '{'), (';', ':'), ('\'', '"'), (',', '<'), ('/', '?')] {
+            let (_, key) = ascii_to_key(plain).unwrap();
+            assert_eq!(ascii_to_key(plain), Some((0, key)));
+            assert_eq!(ascii_to_key(shifted), Some((SHIFT, key)), "{shifted}");
+        }
+        assert_eq!(ascii_to_key('\n'), Some((0, key::ENTER)));
+        assert_eq!(ascii_to_key(' '), Some((0, key::SPACE)));
+    }
+
+    #[test]
+    fn unmapped_characters() {
+        assert_eq!(ascii_to_key('é'), None);
+        assert_eq!(ascii_to_key('\r'), None);
+    }
+
+    #[test]
+    fn repeated_characters_are_released_in_between() {
+        let reports: Vec<_> = Macro::Text("aA!").reports().collect();
+        assert_eq!(
+            reports,
+            [
+                KeyboardReport::key(0, key::A),
+                KeyboardReport::empty(),
+                KeyboardReport::key(SHIFT, key::A),
+                KeyboardReport::empty(),
+                KeyboardReport::key(SHIFT, 0x1E),
+                KeyboardReport::empty(),
+            ]
+        );
+
+        let reports: Vec<_> = Macro::Text("oo").reports().collect();
+        assert_eq!(reports, [KeyboardReport::key(0, key::O), KeyboardReport::empty(), KeyboardReport::key(0, key::O), KeyboardReport::empty()]);
+    }
+
+    #[test]
+    fn unmapped_characters_are_skipped() {
+        let reports: Vec<_> = Macro::Text("é\rb£").reports().collect();
+        assert_eq!(reports, [KeyboardReport::key(0, key::B), KeyboardReport::empty()]);
+        assert_eq!(Macro::Text("€").reports().count(), 0);
+    }
+
+    #[test]
+    fn hotkey() {
+        let hotkey = Macro::Hotkey { modifier: modifier::LEFT_CTRL | modifier::LEFT_ALT, key: key::T };
+        let reports: Vec<_> = hotkey.reports().collect();
+        assert_eq!(reports, [KeyboardReport::key(0x05, key::T), KeyboardReport::empty()]);
+    }
+
+    #[test]
+    fn report_bytes() {
+        let report = KeyboardReport { modifier: SHIFT, keys: [key::H, key::I, 0, 0, 0, 0] };
+        assert_eq!(report.to_bytes(), [0x02, 0, 0x0B, 0x0C, 0, 0, 0, 0]);
+        assert_eq!(KeyboardReport::empty().to_bytes(), [0; 8]);
+    }
+}
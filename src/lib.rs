@@ -1 +1,31 @@
 #![no_std]
+
+pub mod apa102;
+pub mod beam;
+pub mod color;
+pub mod dashboard;
+pub mod datalog;
+#[cfg(feature = "defmt-usb")]
+pub mod defmt_usb;
+pub mod display;
+pub mod effects;
+pub mod fat12;
+pub mod framebuffer;
+pub mod input;
+pub mod keyboard;
+pub mod matrix;
+pub mod menu;
+pub mod neopixel;
+pub mod oled;
+pub mod pio_sim;
+pub mod protocol;
+pub mod reactive;
+pub mod scheduler;
+pub mod screens;
+pub mod strip;
+pub mod text;
+pub mod thermometer;
+pub mod tilt;
+pub mod usb_console;
+pub mod usb_msc;
+pub mod ws2812;
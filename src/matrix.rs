@@ -0,0 +1,324 @@
+// LED panels made of a strip wound into a grid, as an embedded-graphics
+// draw target.
+//
+// `Layout` says how the strip runs through the panel and which way up it's
+// mounted, and maps (x, y) as drawn to the pixel's place along the strip.
+// `Matrix` draws in full colour onto any `Strip`. For the monochrome
+// drawing code in `display`, `Matrix::ink` gives a draw target that lights
+// pixels in one colour, and it can flush, so it works with `Screen`.
+//
+// `Marquee` scrolls text across any monochrome target, LED panel or OLED.
+
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::{BinaryColor, Rgb888};
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+use rgb::RGB8;
+
+use crate::color;
+use crate::display::{FontSize, Flush};
+use crate::strip::Strip;
+
+/// How the strip runs through the panel, starting from the top left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wiring {
+    /// Every row left to right.
+    Rows,
+    /// Every column top to bottom.
+    Columns,
+    /// Left to right, then back right to left on the next row, and so on.
+    SerpentineRows,
+    /// Down the first column, up the next, and so on.
+    SerpentineColumns,
+}
+
+/// Turns what's drawn clockwise on the panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// Of the panel as wired, before rotation.
+    pub width: u32,
+    pub height: u32,
+    pub wiring: Wiring,
+    pub rotation: Rotation,
+    /// Flip left to right and top to bottom, after rotating.
+    pub mirror_x: bool,
+    pub mirror_y: bool,
+}
+
+impl Layout {
+    pub const fn new(width: u32, height: u32, wiring: Wiring) -> Self {
+        Self { width, height, wiring, rotation: Rotation::None, mirror_x: false, mirror_y: false }
+    }
+
+    /// Size as drawn, which is turned round by a quarter rotation.
+    pub fn size(&self) -> Size {
+        match self.rotation {
+            Rotation::None | Rotation::Half => Size::new(self.width, self.height),
+            Rotation::Quarter | Rotation::ThreeQuarters => Size::new(self.height, self.width),
+        }
+    }
+
+    /// Where (x, y) as drawn is along the strip, `None` if it's off the
+    /// panel.
+    pub fn index(&self, point: Point) -> Option<usize> {
+        let size = self.size();
+        let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y)) else {
+            return None;
+        };
+        if x >= size.width || y >= size.height {
+            return None;
+        }
+        let x = if self.mirror_x { size.width - 1 - x } else { x };
+        let y = if self.mirror_y { size.height - 1 - y } else { y };
+
+        let (w, h) = (self.width, self.height);
+        let (x, y) = match self.rotation {
+            Rotation::None => (x, y),
+            Rotation::Quarter => (w - 1 - y, x),
+            Rotation::Half => (w - 1 - x, h - 1 - y),
+            Rotation::ThreeQuarters => (y, h - 1 - x),
+        };
+
+        let index = match self.wiring {
+            Wiring::Rows => y * w + x,
+            Wiring::Columns => x * h + y,
+            Wiring::SerpentineRows if y % 2 == 1 => y * w + (w - 1 - x),
+            Wiring::SerpentineRows => y * w + x,
+            Wiring::SerpentineColumns if x % 2 == 1 => x * h + (h - 1 - y),
+            Wiring::SerpentineColumns => x * h + y,
+        };
+        Some(index as usize)
+    }
+}
+
+pub struct Matrix<S, const N: usize> {
+    strip: S,
+    layout: Layout,
+}
+
+impl<S: Strip<N>, const N: usize> Matrix<S, N> {
+    /// Panics if the panel has more pixels than the strip.
+    pub fn new(strip: S, layout: Layout) -> Self {
+        assert!((layout.width * layout.height) as usize <= N, "panel bigger than the strip");
+        Self { strip, layout }
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// For brightness, gamma and the power limit.
+    pub fn strip(&mut self) -> &mut S {
+        &mut self.strip
+    }
+
+    pub fn release(self) -> S {
+        self.strip
+    }
+
+    pub fn pixel(&self, point: Point) -> Option<RGB8> {
+        self.layout.index(point).map(|i| self.strip.pixels()[i])
+    }
+
+    pub fn set_pixel(&mut self, point: Point, color: RGB8) {
+        if let Some(i) = self.layout.index(point) {
+            self.strip.pixels_mut()[i] = color;
+        }
+    }
+
+    /// Sends what's been drawn to the panel.
+    pub fn write(&mut self) -> Result<(), S::Error> {
+        self.strip.write()
+    }
+
+    /// A monochrome view of the panel that draws lit pixels in `color`.
+    pub fn ink(&mut self, color: RGB8) -> Ink<'_, S, N> {
+        Ink { matrix: self, color }
+    }
+}
+
+impl<S: Strip<N>, const N: usize> OriginDimensions for Matrix<S, N> {
+    fn size(&self) -> Size {
+        self.layout.size()
+    }
+}
+
+impl<S: Strip<N>, const N: usize> DrawTarget for Matrix<S, N> {
+    type Color = Rgb888;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.set_pixel(point, RGB8::new(color.r(), color.g(), color.b()));
+        }
+        Ok(())
+    }
+}
+
+pub struct Ink<'a, S, const N: usize> {
+    matrix: &'a mut Matrix<S, N>,
+    color: RGB8,
+}
+
+impl<S: Strip<N>, const N: usize> OriginDimensions for Ink<'_, S, N> {
+    fn size(&self) -> Size {
+        self.matrix.size()
+    }
+}
+
+impl<S: Strip<N>, const N: usize> DrawTarget for Ink<'_, S, N> {
+    type Color = BinaryColor;
+    // Drawing can't fail, but flushing can.
+    type Error = S::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, on) in pixels {
+            let color = if on.is_on() { self.color } else { color::BLACK };
+            self.matrix.set_pixel(point, color);
+        }
+        Ok(())
+    }
+}
+
+impl<S: Strip<N>, const N: usize> Flush for Ink<'_, S, N> {
+    fn flush(&mut self) -> Result<(), S::Error> {
+        self.matrix.write()
+    }
+}
+
+/// Text scrolling from right to left across a monochrome target, centred
+/// vertically. It comes in from off the right edge and starts again once
+/// it's gone off the left.
+pub struct Marquee<'a> {
+    text: &'a str,
+    size: FontSize,
+    offset: u32,
+}
+
+impl<'a> Marquee<'a> {
+    pub fn new(text: &'a str, size: FontSize) -> Self {
+        Self { text, size, offset: 0 }
+    }
+
+    /// Clears `target` and draws the text where it's got to.
+    pub fn draw<D: DrawTarget<Color = BinaryColor>>(&self, target: &mut D) -> Result<(), D::Error> {
+        let area = target.bounding_box();
+        target.clear(BinaryColor::Off)?;
+        let position = Point::new(area.top_left.x + area.size.width as i32 - self.offset as i32, area.center().y);
+        let style = MonoTextStyle::new(self.size.font(), BinaryColor::On);
+        Text::with_baseline(self.text, position, style, Baseline::Middle).draw(target)?;
+        Ok(())
+    }
+
+    /// Moves the text a pixel to the left on a target `width` pixels wide.
+    pub fn step(&mut self, width: u32) {
+        let text_width = self.text.chars().count() as u32 * self.size.char_size().width;
+        self.offset = (self.offset + 1) % (width + text_width);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    // The strip index of every point as drawn, a row at a time.
+    fn grid(layout: Layout) -> Vec<Vec<usize>> {
+        let size = layout.size();
+        (0..size.height as i32)
+            .map(|y| (0..size.width as i32).map(|x| layout.index(Point::new(x, y)).unwrap()).collect())
+            .collect()
+    }
+
+    fn panel(wiring: Wiring) -> Layout {
+        Layout::new(3, 2, wiring)
+    }
+
+    #[test]
+    fn wirings() {
+        assert_eq!(grid(panel(Wiring::Rows)), [[0, 1, 2], [3, 4, 5]]);
+        assert_eq!(grid(panel(Wiring::Columns)), [[0, 2, 4], [1, 3, 5]]);
+        assert_eq!(grid(panel(Wiring::SerpentineRows)), [[0, 1, 2], [5, 4, 3]]);
+        assert_eq!(grid(panel(Wiring::SerpentineColumns)), [[0, 3, 4], [1, 2, 5]]);
+    }
+
+    #[test]
+    fn rotations() {
+        let rotated = |rotation| Layout { rotation, ..panel(Wiring::Rows) };
+
+        assert_eq!(rotated(Rotation::None).size(), Size::new(3, 2));
+        assert_eq!(grid(rotated(Rotation::None)), [[0, 1, 2], [3, 4, 5]]);
+
+        // Turned clockwise, the top left as drawn ends up top right.
+        assert_eq!(rotated(Rotation::Quarter).size(), Size::new(2, 3));
+        assert_eq!(grid(rotated(Rotation::Quarter)), [[2, 5], [1, 4], [0, 3]]);
+
+        assert_eq!(rotated(Rotation::Half).size(), Size::new(3, 2));
+        assert_eq!(grid(rotated(Rotation::Half)), [[5, 4, 3], [2, 1, 0]]);
+
+        assert_eq!(rotated(Rotation::ThreeQuarters).size(), Size::new(2, 3));
+        assert_eq!(grid(rotated(Rotation::ThreeQuarters)), [[3, 0], [4, 1], [5, 2]]);
+    }
+
+    #[test]
+    fn rotations_combine_with_wiring() {
+        let layout = Layout { rotation: Rotation::Quarter, ..panel(Wiring::SerpentineRows) };
+        assert_eq!(grid(layout), [[2, 3], [1, 4], [0, 5]]);
+    }
+
+    #[test]
+    fn mirroring() {
+        let layout = Layout { mirror_x: true, ..panel(Wiring::Rows) };
+        assert_eq!(grid(layout), [[2, 1, 0], [5, 4, 3]]);
+        let layout = Layout { mirror_y: true, ..panel(Wiring::Rows) };
+        assert_eq!(grid(layout), [[3, 4, 5], [0, 1, 2]]);
+        let layout = Layout { mirror_x: true, mirror_y: true, ..panel(Wiring::Rows) };
+        assert_eq!(grid(layout), grid(Layout { rotation: Rotation::Half, ..panel(Wiring::Rows) }));
+
+        // Mirrored as drawn, after the rotation.
+        let layout = Layout { rotation: Rotation::Quarter, mirror_x: true, ..panel(Wiring::Rows) };
+        assert_eq!(grid(layout), [[5, 2], [4, 1], [3, 0]]);
+    }
+
+    #[test]
+    fn every_pixel_once() {
+        for wiring in [Wiring::Rows, Wiring::Columns, Wiring::SerpentineRows, Wiring::SerpentineColumns] {
+            for rotation in [Rotation::None, Rotation::Quarter, Rotation::Half, Rotation::ThreeQuarters] {
+                let layout = Layout { rotation, mirror_x: true, ..Layout::new(4, 3, wiring) };
+                let mut indices: Vec<usize> = grid(layout).concat();
+                indices.sort();
+                assert_eq!(indices, (0..12).collect::<Vec<_>>(), "{wiring:?} {rotation:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn off_the_panel() {
+        let layout = panel(Wiring::Rows);
+        for point in [Point::new(-1, 0), Point::new(0, -1), Point::new(3, 0), Point::new(0, 2)] {
+            assert_eq!(layout.index(point), None, "{point:?}");
+        }
+
+        // Going by the size as drawn.
+        let layout = Layout { rotation: Rotation::Quarter, ..panel(Wiring::Rows) };
+        assert_eq!(layout.index(Point::new(2, 0)), None);
+        assert_eq!(layout.index(Point::new(1, 2)), Some(3));
+        assert_eq!(layout.index(Point::new(0, 3)), None);
+    }
+}
@@ -0,0 +1,427 @@
+// Menus on the OLED, driven by the three day3 buttons.
+//
+// Menus are static data: a `Menu` is a title and a list of `Item`s, and
+// items that edit something refer to a slot in a plain `[i32]` owned by the
+// caller. `Navigator` keeps track of where we are in the tree and turns
+// button presses into changes to those values without touching any
+// hardware, so the same logic runs on the host. `Navigator::draw` renders
+// the current menu through `Screen`.
+//
+// With only up, select and down to work with, every submenu starts with a
+// "Back" row. Numbers and choices are edited in place: select starts
+// editing, up and down change the value and select again stores it.
+
+use core::fmt::Write;
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Alignment;
+use heapless::{String, Vec};
+
+use crate::display::{Flush, FontSize, Screen};
+
+/// How deep menus can nest, including the root.
+pub const MAX_DEPTH: usize = 4;
+
+const STATUS_HEIGHT: u32 = 12;
+const ROW_HEIGHT: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Up,
+    Select,
+    Down,
+}
+
+pub struct Menu {
+    pub title: &'static str,
+    pub items: &'static [Item],
+}
+
+pub struct Item {
+    pub label: &'static str,
+    pub kind: ItemKind,
+}
+
+pub enum ItemKind {
+    Submenu(&'static Menu),
+    Number { slot: usize, min: i32, max: i32, step: i32 },
+    /// Stored as 0 or 1.
+    Toggle { slot: usize },
+    /// Stored as an index into `options`.
+    Choice { slot: usize, options: &'static [&'static str] },
+    /// Nothing to edit, selecting it is reported as `Event::Action`.
+    Action(u8),
+}
+
+impl ItemKind {
+    // Next value while editing, `up` counts up. Numbers stop at their
+    // limits, choices wrap round.
+    fn step(&self, value: i32, up: bool) -> i32 {
+        match *self {
+            ItemKind::Number { min, max, step, .. } => {
+                let value = if up { value.saturating_add(step) } else { value.saturating_sub(step) };
+                value.clamp(min, max)
+            }
+            ItemKind::Choice { options, .. } => {
+                let value = if up { value + 1 } else { value - 1 };
+                value.rem_euclid(options.len() as i32)
+            }
+            _ => value,
+        }
+    }
+}
+
+/// Something the caller needs to act on after a button press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Changed { slot: usize, value: i32 },
+    Action(u8),
+}
+
+/// Rows of items that fit below the status bar on a panel `height` pixels
+/// tall.
+pub fn visible_rows(height: u32) -> usize {
+    (height.saturating_sub(STATUS_HEIGHT) / ROW_HEIGHT) as usize
+}
+
+#[derive(Clone, Copy)]
+struct Level {
+    menu: &'static Menu,
+    selected: usize,
+    scroll: usize,
+}
+
+pub struct Navigator {
+    stack: Vec<Level, MAX_DEPTH>,
+    rows: usize,
+    editing: Option<i32>,
+}
+
+impl Navigator {
+    /// `rows` is how many items fit on screen at once, see `visible_rows`.
+    pub fn new(root: &'static Menu, rows: usize) -> Self {
+        let mut stack = Vec::new();
+        stack.push(Level { menu: root, selected: 0, scroll: 0 }).ok();
+        Self { stack, rows: rows.max(1), editing: None }
+    }
+
+    fn level(&self) -> &Level {
+        self.stack.last().unwrap()
+    }
+
+    fn level_mut(&mut self) -> &mut Level {
+        self.stack.last_mut().unwrap()
+    }
+
+    fn has_back_row(&self) -> bool {
+        self.stack.len() > 1
+    }
+
+    fn row_count(&self) -> usize {
+        self.level().menu.items.len() + self.has_back_row() as usize
+    }
+
+    // The item on a row, `None` for the back row.
+    fn item(&self, row: usize) -> Option<&'static Item> {
+        let menu = self.level().menu;
+        if self.has_back_row() {
+            row.checked_sub(1).and_then(|i| menu.items.get(i))
+        } else {
+            menu.items.get(row)
+        }
+    }
+
+    pub fn menu(&self) -> &'static Menu {
+        self.level().menu
+    }
+
+    /// 1 at the root menu.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// The highlighted row, counting the back row in submenus.
+    pub fn selected(&self) -> usize {
+        self.level().selected
+    }
+
+    /// The value being edited, before it's stored.
+    pub fn editing(&self) -> Option<i32> {
+        self.editing
+    }
+
+    fn select(&mut self, row: usize) {
+        let rows = self.rows;
+        let level = self.level_mut();
+        level.selected = row;
+        if row < level.scroll {
+            level.scroll = row;
+        } else if row >= level.scroll + rows {
+            level.scroll = row + 1 - rows;
+        }
+    }
+
+    /// Handles one button press. Panics if an item's slot is out of range
+    /// for `values`.
+    pub fn press(&mut self, button: Button, values: &mut [i32]) -> Option<Event> {
+        let selected = self.selected();
+
+        if let Some(pending) = self.editing {
+            // Only numbers and choices are ever edited, never the back row.
+            let item = self.item(selected)?;
+            match (button, &item.kind) {
+                (Button::Select, ItemKind::Number { slot, .. } | ItemKind::Choice { slot, .. }) => {
+                    self.editing = None;
+                    values[*slot] = pending;
+                    return Some(Event::Changed { slot: *slot, value: pending });
+                }
+                (button, kind) => self.editing = Some(kind.step(pending, button == Button::Up)),
+            }
+            return None;
+        }
+
+        match button {
+            Button::Up => self.select(selected.saturating_sub(1)),
+            Button::Down => self.select((selected + 1).min(self.row_count() - 1)),
+            Button::Select => match self.item(selected).map(|item| &item.kind) {
+                None => {
+                    self.stack.pop();
+                }
+                Some(ItemKind::Submenu(menu)) => {
+                    // Deeper than MAX_DEPTH is a mistake in the menu
+                    // definition, stay put rather than lose our place.
+                    self.stack.push(Level { menu, selected: 0, scroll: 0 }).ok();
+                }
+                Some(ItemKind::Number { slot, .. } | ItemKind::Choice { slot, .. }) => {
+                    self.editing = Some(values[*slot]);
+                }
+                Some(ItemKind::Toggle { slot }) => {
+                    values[*slot] = (values[*slot] == 0) as i32;
+                    return Some(Event::Changed { slot: *slot, value: values[*slot] });
+                }
+                Some(ItemKind::Action(id)) => return Some(Event::Action(*id)),
+            },
+        }
+        None
+    }
+
+    /// Redraws the whole menu with `status` at the right of the status bar.
+    /// Doesn't flush.
+    pub fn draw<D: Flush>(&self, screen: &mut Screen<D>, values: &[i32], status: &str) -> Result<(), D::Error> {
+        let width = screen.size().width as i32;
+        let level = self.level();
+
+        screen.clear()?;
+        screen.text(level.menu.title, Point::zero(), FontSize::Small, Alignment::Left)?;
+        screen.text(status, Point::new(width, 0), FontSize::Small, Alignment::Right)?;
+        screen.line(Point::new(0, 10), Point::new(width - 1, 10), 1)?;
+
+        let rows = (level.scroll..self.row_count()).take(self.rows);
+        for (i, row) in rows.enumerate() {
+            let y = (STATUS_HEIGHT + i as u32 * ROW_HEIGHT) as i32;
+            if row == level.selected {
+                screen.text(">", Point::new(0, y), FontSize::Small, Alignment::Left)?;
+            }
+
+            let Some(item) = self.item(row) else {
+                screen.text("Back", Point::new(8, y), FontSize::Small, Alignment::Left)?;
+                continue;
+            };
+            screen.text(item.label, Point::new(8, y), FontSize::Small, Alignment::Left)?;
+
+            let editing = self.editing.filter(|_| row == level.selected);
+            let value = value_text(&item.kind, values, editing);
+            screen.text(&value, Point::new(width, y), FontSize::Small, Alignment::Right)?;
+        }
+        Ok(())
+    }
+}
+
+// What's shown at the right of a row, in brackets while being edited.
+fn value_text(kind: &ItemKind, values: &[i32], editing: Option<i32>) -> String<24> {
+    let mut text = String::new();
+    let (slot, open, close) = match kind {
+        ItemKind::Submenu(_) => {
+            text.push('>').ok();
+            return text;
+        }
+        ItemKind::Action(_) => return text,
+        ItemKind::Number { slot, .. } | ItemKind::Toggle { slot } | ItemKind::Choice { slot, .. } => match editing {
+            Some(_) => (*slot, "[", "]"),
+            None => (*slot, "", ""),
+        },
+    };
+    let value = editing.unwrap_or(values[slot]);
+
+    // A value too long for the buffer is left out.
+    let _ = match kind {
+        ItemKind::Toggle { .. } => write!(text, "{}{}{}", open, if value != 0 { "On" } else { "Off" }, close),
+        ItemKind::Choice { options, .. } => {
+            let option = options.get(value as usize).unwrap_or(&"?");
+            write!(text, "{}{}{}", open, option, close)
+        }
+        _ => write!(text, "{}{}{}", open, value, close),
+    };
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Button::{Down, Select, Up};
+
+    const BRIGHTNESS: usize = 0;
+    const SOUND: usize = 1;
+    const COLOUR: usize = 2;
+    const DEEP: usize = 3;
+
+    static DISPLAY: Menu = Menu {
+        title: "Display",
+        items: &[
+            Item { label: "Brightness", kind: ItemKind::Number { slot: BRIGHTNESS, min: 0, max: 10, step: 3 } },
+            Item { label: "Colour", kind: ItemKind::Choice { slot: COLOUR, options: &["Red", "Green", "Blue"] } },
+        ],
+    };
+
+    static MANY: Menu = Menu {
+        title: "Many",
+        items: &[
+            Item { label: "1", kind: ItemKind::Action(1) },
+            Item { label: "2", kind: ItemKind::Action(2) },
+            Item { label: "3", kind: ItemKind::Action(3) },
+            Item { label: "4", kind: ItemKind::Action(4) },
+            Item { label: "5", kind: ItemKind::Action(5) },
+        ],
+    };
+
+    static ROOT: Menu = Menu {
+        title: "Settings",
+        items: &[
+            Item { label: "Display", kind: ItemKind::Submenu(&DISPLAY) },
+            Item { label: "Sound", kind: ItemKind::Toggle { slot: SOUND } },
+            Item { label: "Reset", kind: ItemKind::Action(7) },
+            Item { label: "Many", kind: ItemKind::Submenu(&MANY) },
+            Item { label: "Deep", kind: ItemKind::Number { slot: DEEP, min: -5, max: 5, step: 1 } },
+        ],
+    };
+
+    fn press(nav: &mut Navigator, values: &mut [i32], buttons: &[Button]) -> Option<Event> {
+        buttons.iter().fold(None, |_, &button| nav.press(button, values))
+    }
+
+    #[test]
+    fn into_a_submenu_and_back() {
+        let mut nav = Navigator::new(&ROOT, 3);
+        let mut values = [0; 4];
+        assert_eq!(nav.press(Select, &mut values), None);
+        assert_eq!(nav.depth(), 2);
+        assert_eq!(nav.menu().title, "Display");
+        // The back row comes first and is where we start.
+        assert_eq!(nav.selected(), 0);
+        assert!(nav.item(0).is_none());
+        assert_eq!(nav.row_count(), 3);
+
+        nav.press(Select, &mut values);
+        assert_eq!(nav.depth(), 1);
+        assert_eq!(nav.menu().title, "Settings");
+        // Back where we left off.
+        assert_eq!(nav.selected(), 0);
+    }
+
+    #[test]
+    fn up_and_down_stop_at_the_ends() {
+        let mut nav = Navigator::new(&ROOT, 3);
+        let mut values = [0; 4];
+        nav.press(Up, &mut values);
+        assert_eq!(nav.selected(), 0);
+        press(&mut nav, &mut values, &[Down; 10]);
+        assert_eq!(nav.selected(), ROOT.items.len() - 1);
+    }
+
+    #[test]
+    fn scrolling_follows_the_selection() {
+        let mut nav = Navigator::new(&ROOT, 3);
+        let mut values = [0; 4];
+        press(&mut nav, &mut values, &[Down, Down]);
+        assert_eq!(nav.level().scroll, 0);
+        nav.press(Down, &mut values);
+        assert_eq!((nav.selected(), nav.level().scroll), (3, 1));
+        nav.press(Down, &mut values);
+        assert_eq!((nav.selected(), nav.level().scroll), (4, 2));
+        press(&mut nav, &mut values, &[Up, Up]);
+        assert_eq!((nav.selected(), nav.level().scroll), (2, 2));
+        nav.press(Up, &mut values);
+        assert_eq!((nav.selected(), nav.level().scroll), (1, 1));
+
+        // Each level keeps its own window.
+        press(&mut nav, &mut values, &[Down, Down, Select]);
+        assert_eq!(nav.menu().title, "Many");
+        press(&mut nav, &mut values, &[Down; 5]);
+        assert_eq!((nav.selected(), nav.level().scroll), (5, 3));
+        press(&mut nav, &mut values, &[Up; 5]);
+        assert_eq!((nav.selected(), nav.level().scroll), (0, 0));
+        nav.press(Select, &mut values);
+        assert_eq!((nav.selected(), nav.level().scroll), (3, 1));
+    }
+
+    #[test]
+    fn numbers_stop_at_their_limits() {
+        let mut nav = Navigator::new(&ROOT, 3);
+        let mut values = [5, 0, 0, 0];
+        press(&mut nav, &mut values, &[Select, Down, Select]);
+        assert_eq!(nav.editing(), Some(5));
+        press(&mut nav, &mut values, &[Up, Up]);
+        assert_eq!(nav.editing(), Some(10));
+        // Not stored until it's selected.
+        assert_eq!(values[BRIGHTNESS], 5);
+        assert_eq!(nav.press(Select, &mut values), Some(Event::Changed { slot: BRIGHTNESS, value: 10 }));
+        assert_eq!((values[BRIGHTNESS], nav.editing()), (10, None));
+
+        press(&mut nav, &mut values, &[Select, Down, Down, Down, Down]);
+        assert_eq!(nav.editing(), Some(0));
+        nav.press(Select, &mut values);
+        assert_eq!(values[BRIGHTNESS], 0);
+
+        press(&mut nav, &mut values, &[Up, Select, Down, Down, Down, Down, Select]);
+        assert_eq!(nav.editing(), Some(0));
+        press(&mut nav, &mut values, &[Down; 8]);
+        assert_eq!(nav.press(Select, &mut values), Some(Event::Changed { slot: DEEP, value: -5 }));
+    }
+
+    #[test]
+    fn choices_wrap_round() {
+        let mut nav = Navigator::new(&ROOT, 3);
+        let mut values = [0; 4];
+        press(&mut nav, &mut values, &[Select, Down, Down, Select]);
+        nav.press(Up, &mut values);
+        assert_eq!(nav.editing(), Some(1));
+        press(&mut nav, &mut values, &[Up, Up]);
+        assert_eq!(nav.editing(), Some(0));
+        nav.press(Down, &mut values);
+        assert_eq!(nav.editing(), Some(2));
+        assert_eq!(nav.press(Select, &mut values), Some(Event::Changed { slot: COLOUR, value: 2 }));
+        assert_eq!(value_text(&DISPLAY.items[1].kind, &values, None), "Blue");
+        assert_eq!(value_text(&DISPLAY.items[1].kind, &values, Some(0)), "[Red]");
+    }
+
+    #[test]
+    fn toggles_flip_straight_away() {
+        let mut nav = Navigator::new(&ROOT, 3);
+        let mut values = [0; 4];
+        nav.press(Down, &mut values);
+        assert_eq!(nav.press(Select, &mut values), Some(Event::Changed { slot: SOUND, value: 1 }));
+        assert_eq!(nav.editing(), None);
+        assert_eq!(nav.press(Select, &mut values), Some(Event::Changed { slot: SOUND, value: 0 }));
+        assert_eq!(values[SOUND], 0);
+    }
+
+    #[test]
+    fn actions_are_reported() {
+        let mut nav = Navigator::new(&ROOT, 3);
+        let mut values = [0; 4];
+        assert_eq!(press(&mut nav, &mut values, &[Down, Down, Select]), Some(Event::Action(7)));
+        assert_eq!(press(&mut nav, &mut values, &[Down, Select, Down, Down, Select]), Some(Event::Action(2)));
+        assert_eq!(nav.depth(), 2);
+        assert_eq!(values, [0; 4]);
+    }
+}
@@ -0,0 +1,166 @@
+// WS2812 ("NeoPixel") strips driven by a PIO state machine, as in day12.
+//
+// The PIO program, from `Chipset::program`, clocks out one bit per 10 PIO
+// cycles, so the state machine runs at ten times the bit rate. Each pixel
+// goes into the TX FIFO as its own word, packed for the chipset with the
+// colour bits at the top, and the state machine autopulls after each
+// pixel's worth of bits, so nothing needs padding whatever the strip
+// length.
+//
+// Frames are fed to the FIFO by DMA from two word buffers in turn: `write`
+// packs the next frame into whichever buffer isn't being sent, starts it
+// going and returns straight away. It only has to wait if the previous
+// frame is still going out or hasn't latched yet.
+//
+// The strip latches once the data line has been low for a while. When a
+// transfer is seen to have finished, the time it'll be safe to send again
+// is noted, and the next transfer doesn't start before then.
+
+use core::convert::Infallible;
+
+use fugit::{ExtU64, HertzU32};
+use rp_pico::hal;
+use hal::dma::{single_buffer, SingleChannel};
+use hal::gpio::{AnyPin, Pin};
+use hal::pio::{
+    PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine, StateMachineIndex, Tx, UninitStateMachine, PIO,
+};
+use hal::timer::Instant;
+use hal::Timer;
+
+use crate::strip::{Frame, Strip};
+use crate::ws2812::{self, Chipset};
+
+// Newer WS2812B parts want the line low for 280 µs before they latch,
+// older ones only 50 µs.
+const RESET_US: u64 = 300;
+// The last pixel is still being shifted out once the FIFO is empty, this
+// is long enough at the slowest bit rate.
+const LAST_PIXEL_US: u64 = 32 * 1_000_000 / ws2812::SLOW_BIT_RATE as u64 + 1;
+
+type Buffer<const N: usize> = &'static mut [u32; N];
+
+// The DMA channel, the buffer being sent and the FIFO move into the
+// transfer while it runs and come back out when it's done.
+enum Output<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> {
+    Idle(CH, Buffer<N>, Tx<(P, SM)>),
+    Sending(single_buffer::Transfer<CH, Buffer<N>, Tx<(P, SM)>>),
+}
+
+pub struct NeoPixelStrip<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> {
+    _sm: StateMachine<(P, SM), Running>,
+    // Only `None` part way through starting or finishing a transfer.
+    output: Option<Output<P, SM, CH, N>>,
+    spare: Option<Buffer<N>>,
+    timer: Timer,
+    chipset: Chipset,
+    latched_at: Instant,
+    frame: Frame<N>,
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> NeoPixelStrip<P, SM, CH, N> {
+    /// Sets up `sm` to drive a strip of `N` pixels on `pin`, fed from
+    /// `buffers` by the `dma` channel. `clock_freq` is the system clock,
+    /// the PIO clock divisor is worked out from it. Panics if it's too slow
+    /// or fast to get the chipset's bit rate.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<I>(
+        pin: I,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        clock_freq: HertzU32,
+        timer: Timer,
+        chipset: Chipset,
+        dma: CH,
+        buffers: &'static mut [[u32; N]; 2],
+    ) -> Self
+    where
+        I: AnyPin<Function = P::PinFunction>,
+    {
+        let installed = pio.install(&chipset.program()).unwrap();
+
+        let (int, frac) = chipset
+            .clock_divisor(clock_freq.to_Hz())
+            .expect("system clock out of range for the LEDs")
+            .fixed_point();
+
+        let pin: Pin<I::Id, P::PinFunction, I::Pull> = pin.into();
+        let pin_id = pin.id().num;
+
+        let (mut sm, _, tx) = PIOBuilder::from_program(installed)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            // The register field is 5 bits wide, 0 means 32.
+            .pull_threshold(chipset.order().bits() % 32)
+            .side_set_pin_base(pin_id)
+            .clock_divisor_fixed_point(int, frac)
+            .build(sm);
+        sm.set_pindirs([(pin_id, PinDir::Output)]);
+
+        let [first, second] = buffers;
+
+        Self {
+            _sm: sm.start(),
+            output: Some(Output::Idle(dma, first, tx)),
+            spare: Some(second),
+            timer,
+            chipset,
+            latched_at: timer.get_counter(),
+            frame: Frame::new(),
+        }
+    }
+
+    /// True once the last frame has gone out and latched, so `write` won't
+    /// have to wait.
+    pub fn is_done(&mut self) -> bool {
+        if let Some(Output::Sending(transfer)) = &self.output {
+            if !transfer.is_done() {
+                return false;
+            }
+            self.finish();
+        }
+        self.timer.get_counter() >= self.latched_at
+    }
+
+    // Waits for the frame being sent, if there is one, to leave the FIFO
+    // and notes when the strip will have latched it.
+    fn finish(&mut self) {
+        if let Some(Output::Sending(transfer)) = self.output.take() {
+            let (dma, buffer, tx) = transfer.wait();
+            while !tx.is_empty() {}
+            self.latched_at = self.timer.get_counter() + (LAST_PIXEL_US + RESET_US).micros();
+            self.output = Some(Output::Idle(dma, buffer, tx));
+        }
+    }
+}
+
+impl<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> Strip<N> for NeoPixelStrip<P, SM, CH, N> {
+    type Error = Infallible;
+
+    fn frame(&self) -> &Frame<N> {
+        &self.frame
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame<N> {
+        &mut self.frame
+    }
+
+    /// Starts sending the pixels to the strip and returns without waiting
+    /// for them to go out. Only waits if the previous frame hasn't finished
+    /// going out and latching.
+    fn write(&mut self) -> Result<(), Infallible> {
+        let next = self.spare.take().unwrap();
+        for (word, pixel) in next.iter_mut().zip(self.frame.corrected()) {
+            *word = self.chipset.pack(pixel);
+        }
+
+        self.finish();
+        while self.timer.get_counter() < self.latched_at {}
+
+        if let Some(Output::Idle(dma, sent, tx)) = self.output.take() {
+            self.spare = Some(sent);
+            self.output = Some(Output::Sending(single_buffer::Config::new(dma, next, tx).start()));
+        }
+        Ok(())
+    }
+}
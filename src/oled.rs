@@ -0,0 +1,166 @@
+// Setting up the SSD1306 panel from a runtime configuration.
+//
+// ssd1306 encodes the panel size in the type, which means swapping panels
+// meant editing every place the type was spelled out. `Oled` hides the size
+// behind an enum so the size can come from `DisplayConfig` instead, and
+// `DisplayConfig::build` checks something actually answers on the bus
+// before talking to it.
+
+use display_interface::DisplayError;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_hal::blocking::i2c::Write;
+use fugit::{HertzU32, RateExtU32};
+// The trait shares its name with our config struct below.
+use ssd1306::mode::{BufferedGraphicsMode, DisplayConfig as _};
+use ssd1306::prelude::*;
+use ssd1306::{I2CDisplayInterface, Ssd1306};
+
+use crate::display::Flush;
+
+/// Addresses an SSD1306 can be strapped to.
+pub const ADDRESSES: [u8; 2] = [0x3C, 0x3D];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelSize {
+    Size128x64,
+    Size128x32,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Nothing acknowledged at the configured address, or at either of the
+    /// usual addresses when auto detecting.
+    PanelNotFound,
+    Interface(DisplayError),
+}
+
+impl From<DisplayError> for Error {
+    fn from(e: DisplayError) -> Self {
+        Error::Interface(e)
+    }
+}
+
+/// Everything about the panel that varies between boards. The SDA and SCL
+/// pins are typed in rp2040-hal, so they're chosen where the I2C bus is
+/// created, using `bus_speed` from here.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayConfig {
+    pub size: PanelSize,
+    pub rotation: DisplayRotation,
+    /// `None` probes `ADDRESSES` and uses the first that responds.
+    pub address: Option<u8>,
+    pub bus_speed: HertzU32,
+}
+
+impl Default for DisplayConfig {
+    /// The 0.91" 128x32 panel that comes with the calendar.
+    fn default() -> Self {
+        Self {
+            size: PanelSize::Size128x32,
+            rotation: DisplayRotation::Rotate0,
+            address: None,
+            bus_speed: 400.kHz(),
+        }
+    }
+}
+
+/// Returns true if a device acknowledges `address`.
+pub fn probe<I: Write>(i2c: &mut I, address: u8) -> bool {
+    // Control byte followed by the NOP command, harmless if it is a panel.
+    i2c.write(address, &[0x00, 0xE3]).is_ok()
+}
+
+pub fn detect_address<I: Write>(i2c: &mut I) -> Option<u8> {
+    ADDRESSES.into_iter().find(|&address| probe(i2c, address))
+}
+
+impl DisplayConfig {
+    /// Finds the panel, initialises it in buffered graphics mode and clears
+    /// it.
+    pub fn build<I: Write>(&self, mut i2c: I) -> Result<Oled<I>, Error> {
+        let address = match self.address {
+            Some(address) if probe(&mut i2c, address) => address,
+            Some(_) => return Err(Error::PanelNotFound),
+            None => detect_address(&mut i2c).ok_or(Error::PanelNotFound)?,
+        };
+        let interface = I2CDisplayInterface::new_custom_address(i2c, address);
+
+        let mut oled = match self.size {
+            PanelSize::Size128x64 => Oled::Size128x64(
+                Ssd1306::new(interface, DisplaySize128x64, self.rotation).into_buffered_graphics_mode(),
+            ),
+            PanelSize::Size128x32 => Oled::Size128x32(
+                Ssd1306::new(interface, DisplaySize128x32, self.rotation).into_buffered_graphics_mode(),
+            ),
+        };
+        oled.init()?;
+        Ok(oled)
+    }
+}
+
+type Panel<I, SIZE> = Ssd1306<I2CInterface<I>, SIZE, BufferedGraphicsMode<SIZE>>;
+
+// There's only ever one of these and no heap to box into, the size
+// difference is just the 128x64 framebuffer.
+#[allow(clippy::large_enum_variant)]
+pub enum Oled<I> {
+    Size128x64(Panel<I, DisplaySize128x64>),
+    Size128x32(Panel<I, DisplaySize128x32>),
+}
+
+impl<I: Write> Oled<I> {
+    fn init(&mut self) -> Result<(), DisplayError> {
+        match self {
+            Oled::Size128x64(d) => d.init(),
+            Oled::Size128x32(d) => d.init(),
+        }
+    }
+
+    pub fn set_rotation(&mut self, rotation: DisplayRotation) -> Result<(), DisplayError> {
+        match self {
+            Oled::Size128x64(d) => d.set_rotation(rotation),
+            Oled::Size128x32(d) => d.set_rotation(rotation),
+        }
+    }
+}
+
+impl<I: Write> OriginDimensions for Oled<I> {
+    fn size(&self) -> Size {
+        match self {
+            Oled::Size128x64(d) => d.size(),
+            Oled::Size128x32(d) => d.size(),
+        }
+    }
+}
+
+impl<I: Write> DrawTarget for Oled<I> {
+    type Color = BinaryColor;
+    type Error = DisplayError;
+
+    fn draw_iter<P>(&mut self, pixels: P) -> Result<(), Self::Error>
+    where
+        P: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        match self {
+            Oled::Size128x64(d) => d.draw_iter(pixels),
+            Oled::Size128x32(d) => d.draw_iter(pixels),
+        }
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        match self {
+            Oled::Size128x64(d) => d.clear(color),
+            Oled::Size128x32(d) => d.clear(color),
+        }
+    }
+}
+
+impl<I: Write> Flush for Oled<I> {
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        match self {
+            Oled::Size128x64(d) => d.flush(),
+            Oled::Size128x32(d) => d.flush(),
+        }
+    }
+}
@@ -0,0 +1,460 @@
+// A PIO state machine simulator, so programs can be checked on the host
+// without a logic analyser.
+//
+// Only what our programs use is simulated: out, set, jmp, pull and mov
+// between the scratch registers (which covers nop), with side-set, delays,
+// autopull and wrap. Anything else stops the machine with
+// `Error::Unsupported`. There's one state machine with its own TX FIFO and
+// nothing else, no IRQs, no RX and no input pins apart from the jmp pin
+// reading back what the machine drives.
+//
+// Time is counted in state machine cycles. `Config::ns` turns a count of
+// those into nanoseconds for a given system clock. A fractional divisor is
+// taken as its average, the real one stretches some cycles by a system
+// clock cycle to get there.
+
+use pio::{
+    Instruction, InstructionOperands, JmpCondition, MovDestination, MovOperation, MovSource, OutDestination, Program,
+    SetDestination,
+};
+use rp_pico::hal::pio::ShiftDirection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The instruction at this address isn't one we simulate.
+    Unsupported(u8),
+}
+
+/// The state machine settings that matter to the simulator, named after
+/// the `PIOBuilder` methods that set them. Pins are GPIO numbers.
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// Integer and 8 bit fractional parts.
+    pub clock_divisor: (u16, u8),
+    pub out_shift_direction: ShiftDirection,
+    pub autopull: bool,
+    /// 0 means 32, as in the register.
+    pub pull_threshold: u8,
+    pub out_pins: u8,
+    pub set_pins: (u8, u8),
+    pub side_set_pin_base: u8,
+    pub jmp_pin: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clock_divisor: (1, 0),
+            out_shift_direction: ShiftDirection::Left,
+            autopull: false,
+            pull_threshold: 0,
+            out_pins: 0,
+            set_pins: (0, 0),
+            side_set_pin_base: 0,
+            jmp_pin: 0,
+        }
+    }
+}
+
+impl Config {
+    /// How long `cycles` state machine cycles take with the system clock at
+    /// `clock_hz`, in ns.
+    pub fn ns(&self, cycles: u64, clock_hz: u32) -> u64 {
+        let (int, frac) = self.clock_divisor;
+        // An integer part of 0 means 65536.
+        let int = if int == 0 { 1 << 16 } else { int as u128 };
+        let divisor = int << 8 | frac as u128;
+        (cycles as u128 * divisor * 1_000_000_000 / (clock_hz as u128 * 256)) as u64
+    }
+
+    fn threshold(&self) -> u8 {
+        match self.pull_threshold % 32 {
+            0 => 32,
+            bits => bits,
+        }
+    }
+}
+
+pub struct Machine<'a, const N: usize, I> {
+    program: &'a Program<N>,
+    config: Config,
+    tx: I,
+    pc: u8,
+    x: u32,
+    y: u32,
+    osr: u32,
+    // Bits shifted out of the OSR since it was last filled.
+    shifted: u8,
+    delay: u8,
+    stalled: bool,
+    pins: u32,
+    pindirs: u32,
+    cycles: u64,
+}
+
+impl<'a, const N: usize, I: Iterator<Item = u32>> Machine<'a, N, I> {
+    /// Loads `program` at address 0, fed from `tx` as if it were the FIFO.
+    pub fn new(program: &'a Program<N>, config: Config, tx: I) -> Self {
+        Self {
+            program,
+            config,
+            tx,
+            pc: 0,
+            x: 0,
+            y: 0,
+            osr: 0,
+            // The OSR starts empty.
+            shifted: 32,
+            delay: 0,
+            stalled: false,
+            pins: 0,
+            pindirs: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Levels the machine is driving, one bit per GPIO.
+    pub fn pins(&self) -> u32 {
+        self.pins
+    }
+
+    pub fn pindirs(&self) -> u32 {
+        self.pindirs
+    }
+
+    /// Cycles run so far.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// True if the last cycle was spent waiting for the FIFO.
+    pub fn stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Runs one cycle.
+    pub fn step(&mut self) -> Result<(), Error> {
+        self.cycles += 1;
+        if self.delay > 0 {
+            self.delay -= 1;
+            return Ok(());
+        }
+
+        let pc = self.pc;
+        let instruction = self
+            .program
+            .code
+            .get(pc as usize)
+            .and_then(|&word| Instruction::decode(word, self.program.side_set))
+            .ok_or(Error::Unsupported(pc))?;
+
+        // Side-set happens even if the instruction stalls.
+        if let Some(value) = instruction.side_set {
+            let side_set = self.program.side_set;
+            let count = side_set.bits() - side_set.optional() as u8;
+            if side_set.pindirs() {
+                self.pindirs = write_pins(self.pindirs, self.config.side_set_pin_base, count, value as u32);
+            } else {
+                self.pins = write_pins(self.pins, self.config.side_set_pin_base, count, value as u32);
+            }
+        }
+
+        let mut next = if pc == self.program.wrap.source { self.program.wrap.target } else { pc + 1 };
+        let threshold = self.config.threshold();
+
+        match instruction.operands {
+            InstructionOperands::OUT { destination, bit_count } => {
+                if self.config.autopull && self.shifted >= threshold && !self.pull() {
+                    self.stalled = true;
+                    return Ok(());
+                }
+                let bit_count = if bit_count == 0 { 32 } else { bit_count };
+                let data = self.shift_out(bit_count);
+                match destination {
+                    OutDestination::PINS => self.pins = write_pins(self.pins, self.config.out_pins, bit_count, data),
+                    OutDestination::X => self.x = data,
+                    OutDestination::Y => self.y = data,
+                    OutDestination::NULL => {}
+                    OutDestination::PINDIRS => {
+                        self.pindirs = write_pins(self.pindirs, self.config.out_pins, bit_count, data)
+                    }
+                    OutDestination::PC => next = data as u8,
+                    _ => return Err(Error::Unsupported(pc)),
+                }
+            }
+            InstructionOperands::SET { destination, data } => {
+                let (base, count) = self.config.set_pins;
+                match destination {
+                    SetDestination::PINS => self.pins = write_pins(self.pins, base, count, data as u32),
+                    SetDestination::X => self.x = data as u32,
+                    SetDestination::Y => self.y = data as u32,
+                    SetDestination::PINDIRS => self.pindirs = write_pins(self.pindirs, base, count, data as u32),
+                }
+            }
+            InstructionOperands::JMP { condition, address } => {
+                let taken = match condition {
+                    JmpCondition::Always => true,
+                    JmpCondition::XIsZero => self.x == 0,
+                    JmpCondition::XDecNonZero => {
+                        let taken = self.x != 0;
+                        self.x = self.x.wrapping_sub(1);
+                        taken
+                    }
+                    JmpCondition::YIsZero => self.y == 0,
+                    JmpCondition::YDecNonZero => {
+                        let taken = self.y != 0;
+                        self.y = self.y.wrapping_sub(1);
+                        taken
+                    }
+                    JmpCondition::XNotEqualY => self.x != self.y,
+                    JmpCondition::PinHigh => self.pins & 1 << self.config.jmp_pin != 0,
+                    JmpCondition::OutputShiftRegisterNotEmpty => self.shifted < threshold,
+                };
+                if taken {
+                    next = address;
+                }
+            }
+            InstructionOperands::PULL { if_empty, block } => {
+                let skip = if_empty && self.shifted < threshold;
+                if !skip && !self.pull() {
+                    if block {
+                        self.stalled = true;
+                        return Ok(());
+                    }
+                    // A non-blocking pull from an empty FIFO copies X.
+                    self.osr = self.x;
+                    self.shifted = 0;
+                }
+            }
+            InstructionOperands::MOV { destination, op, source } => {
+                let value = match source {
+                    MovSource::X => self.x,
+                    MovSource::Y => self.y,
+                    MovSource::NULL => 0,
+                    MovSource::OSR => self.osr,
+                    _ => return Err(Error::Unsupported(pc)),
+                };
+                let value = match op {
+                    MovOperation::None => value,
+                    MovOperation::Invert => !value,
+                    MovOperation::BitReverse => value.reverse_bits(),
+                };
+                match destination {
+                    MovDestination::X => self.x = value,
+                    MovDestination::Y => self.y = value,
+                    MovDestination::OSR => {
+                        self.osr = value;
+                        self.shifted = 0;
+                    }
+                    _ => return Err(Error::Unsupported(pc)),
+                }
+            }
+            _ => return Err(Error::Unsupported(pc)),
+        }
+
+        self.stalled = false;
+        self.delay = instruction.delay;
+        self.pc = next;
+        Ok(())
+    }
+
+    /// Runs until the machine stalls waiting for the FIFO or `max_cycles`
+    /// have gone by, calling `edge` with the cycle count and new levels
+    /// whenever the pins change. Returns the cycles run.
+    pub fn run(&mut self, max_cycles: u64, mut edge: impl FnMut(u64, u32)) -> Result<u64, Error> {
+        let start = self.cycles;
+        while self.cycles - start < max_cycles {
+            let before = self.pins;
+            self.step()?;
+            if self.pins != before {
+                // The new level shows from the start of this cycle.
+                edge(self.cycles - 1, self.pins);
+            }
+            if self.stalled {
+                break;
+            }
+        }
+        Ok(self.cycles - start)
+    }
+
+    fn pull(&mut self) -> bool {
+        match self.tx.next() {
+            Some(word) => {
+                self.osr = word;
+                self.shifted = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn shift_out(&mut self, bit_count: u8) -> u32 {
+        let bits = bit_count as u32;
+        let mask = if bits == 32 { u32::MAX } else { (1 << bits) - 1 };
+        let data = match self.config.out_shift_direction {
+            ShiftDirection::Left => {
+                let data = self.osr.checked_shr(32 - bits).unwrap_or(0) & mask;
+                self.osr = self.osr.checked_shl(bits).unwrap_or(0);
+                data
+            }
+            ShiftDirection::Right => {
+                let data = self.osr & mask;
+                self.osr = self.osr.checked_shr(bits).unwrap_or(0);
+                data
+            }
+        };
+        self.shifted = (self.shifted + bits as u8).min(32);
+        data
+    }
+}
+
+// Writes the low `count` bits of `value` to the pins from `base` up,
+// wrapping past GPIO 31 like the hardware does.
+fn write_pins(pins: u32, base: u8, count: u8, value: u32) -> u32 {
+    let count = count.min(32) as u32;
+    let mask = if count == 32 { u32::MAX } else { (1 << count) - 1 };
+    let base = base as u32 % 32;
+    (pins & !mask.rotate_left(base)) | (value & mask).rotate_left(base)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use pio::{Assembler, SideSet};
+    use std::vec::Vec;
+
+    // Every pin change as (cycle, pins).
+    fn edges<const N: usize>(machine: &mut Machine<'_, N, impl Iterator<Item = u32>>, max_cycles: u64) -> Vec<(u64, u32)> {
+        let mut edges = Vec::new();
+        machine.run(max_cycles, |cycle, pins| edges.push((cycle, pins))).unwrap();
+        edges
+    }
+
+    #[test]
+    fn autopull_stalls_when_the_fifo_runs_dry() {
+        let mut a = Assembler::<32>::new();
+        a.out(OutDestination::PINS, 8);
+        let program = a.assemble_program();
+        let config = Config { autopull: true, pull_threshold: 8, ..Default::default() };
+        let mut machine = Machine::new(&program, config, [0xAB00_0000].into_iter());
+
+        // One cycle to pull and shift out the top byte, then nothing left
+        // once the threshold is reached.
+        assert_eq!(edges(&mut machine, 100), [(0, 0xAB)]);
+        assert!(machine.stalled());
+        assert_eq!(machine.cycles(), 2);
+        assert_eq!(machine.pins(), 0xAB);
+
+        // It stays stalled without doing anything else.
+        assert_eq!(machine.run(100, |_, _| {}), Ok(1));
+        assert!(machine.stalled());
+    }
+
+    #[test]
+    fn autopull_refills_at_the_threshold() {
+        let mut a = Assembler::<32>::new();
+        a.out(OutDestination::PINS, 8);
+        let program = a.assemble_program();
+        let config = Config { autopull: true, pull_threshold: 16, ..Default::default() };
+        let words = [0x1122_3344, 0x5566_7788];
+        let mut machine = Machine::new(&program, config, words.into_iter());
+
+        // Only the top 16 bits of each word go out before the next pull.
+        assert_eq!(edges(&mut machine, 100), [(0, 0x11), (1, 0x22), (2, 0x55), (3, 0x66)]);
+        assert!(machine.stalled());
+    }
+
+    #[test]
+    fn shifts_right_when_configured_to() {
+        let mut a = Assembler::<32>::new();
+        a.out(OutDestination::PINS, 8);
+        let program = a.assemble_program();
+        let config = Config {
+            out_shift_direction: ShiftDirection::Right,
+            autopull: true,
+            pull_threshold: 16,
+            ..Default::default()
+        };
+        let mut machine = Machine::new(&program, config, [0x1122_3344].into_iter());
+        assert_eq!(edges(&mut machine, 100), [(0, 0x44), (1, 0x33)]);
+    }
+
+    #[test]
+    fn wraps_from_source_to_target() {
+        let mut a = Assembler::<32>::new();
+        let mut wrap_target = a.label();
+        let mut wrap_source = a.label();
+        a.set(SetDestination::PINS, 0);
+        a.bind(&mut wrap_target);
+        a.set(SetDestination::PINS, 1);
+        a.set(SetDestination::PINS, 2);
+        a.bind(&mut wrap_source);
+        a.set(SetDestination::PINS, 3);
+        let program = a.assemble_with_wrap(wrap_source, wrap_target);
+        let config = Config { set_pins: (0, 2), ..Default::default() };
+        let mut machine = Machine::new(&program, config, core::iter::empty());
+
+        // The first instruction runs once and the last never does.
+        assert_eq!(edges(&mut machine, 6), [(1, 1), (2, 2), (3, 1), (4, 2), (5, 1)]);
+        assert!(!machine.stalled());
+        assert_eq!(machine.cycles(), 6);
+    }
+
+    #[test]
+    fn side_set_lasts_through_the_delay() {
+        let mut a = Assembler::<32>::new_with_side_set(SideSet::new(false, 1, false));
+        a.nop_with_delay_and_side_set(2, 1);
+        a.nop_with_delay_and_side_set(0, 0);
+        let program = a.assemble_program();
+        let config = Config { side_set_pin_base: 3, ..Default::default() };
+        let mut machine = Machine::new(&program, config, core::iter::empty());
+
+        // High for the nop and its two cycles of delay, then low for one.
+        assert_eq!(edges(&mut machine, 8), [(0, 1 << 3), (3, 0), (4, 1 << 3), (7, 0)]);
+    }
+
+    #[test]
+    fn side_set_happens_even_when_stalled() {
+        let mut a = Assembler::<32>::new_with_side_set(SideSet::new(false, 1, false));
+        a.out_with_delay_and_side_set(OutDestination::X, 1, 0, 1);
+        let program = a.assemble_program();
+        let config = Config { autopull: true, ..Default::default() };
+        let mut machine = Machine::new(&program, config, core::iter::empty());
+
+        assert_eq!(edges(&mut machine, 10), [(0, 1)]);
+        assert!(machine.stalled());
+    }
+
+    #[test]
+    fn side_set_can_drive_pindirs() {
+        let mut a = Assembler::<32>::new_with_side_set(SideSet::new(false, 1, true));
+        a.nop_with_delay_and_side_set(0, 1);
+        let program = a.assemble_program();
+        let config = Config { side_set_pin_base: 5, ..Default::default() };
+        let mut machine = Machine::new(&program, config, core::iter::empty());
+
+        machine.step().unwrap();
+        assert_eq!(machine.pindirs(), 1 << 5);
+        assert_eq!(machine.pins(), 0);
+    }
+
+    #[test]
+    fn unsupported_instructions_are_an_error() {
+        let mut a = Assembler::<32>::new();
+        a.nop();
+        a.push(false, false);
+        let program = a.assemble_program();
+        let mut machine = Machine::new(&program, Config::default(), core::iter::empty());
+        assert_eq!(machine.run(10, |_, _| {}), Err(Error::Unsupported(1)));
+    }
+
+    #[test]
+    fn ns_counts_the_divisor() {
+        let config = Config { clock_divisor: (1, 0), ..Default::default() };
+        assert_eq!(config.ns(125, 125_000_000), 1000);
+        let config = Config { clock_divisor: (2, 128), ..Default::default() };
+        assert_eq!(config.ns(100, 125_000_000), 2000);
+    }
+}
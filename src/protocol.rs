@@ -0,0 +1,495 @@
+// Framed binary protocol used between the board and the host.
+//
+// A packet on the wire is:
+//
+//   COBS( type | seq | payload... | crc16_hi | crc16_lo ) 0x00
+//
+// The CRC is CRC-16/CCITT-FALSE over the type, sequence number and payload.
+// COBS guarantees the encoded frame contains no zero bytes, so 0x00 is used
+// as the frame delimiter and a decoder can resync after any corruption.
+
+use heapless::Vec;
+
+pub const MAX_PAYLOAD: usize = 8;
+// type + seq + payload + crc
+pub const MAX_RAW: usize = 2 + MAX_PAYLOAD + 2;
+// COBS adds at most one byte per 254, plus the trailing delimiter
+pub const MAX_FRAME: usize = MAX_RAW + 1 + 1;
+
+const TYPE_SENSOR: u8 = 0x01;
+const TYPE_LED: u8 = 0x02;
+const TYPE_NEOPIXEL: u8 = 0x03;
+const TYPE_ACK: u8 = 0x04;
+const TYPE_NACK: u8 = 0x05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    BufferTooSmall,
+    FrameTooLong,
+    Cobs,
+    Crc,
+    UnknownType(u8),
+    Length,
+    /// A field holds a value with no meaning, like an unknown sensor.
+    BadField,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sensor {
+    Temperature,
+    Potentiometer,
+    Light,
+    Pir,
+    BreakBeam,
+    Tilt,
+}
+
+impl Sensor {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Sensor::Temperature => 0,
+            Sensor::Potentiometer => 1,
+            Sensor::Light => 2,
+            Sensor::Pir => 3,
+            Sensor::BreakBeam => 4,
+            Sensor::Tilt => 5,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sensor::Temperature => "temperature",
+            Sensor::Potentiometer => "potentiometer",
+            Sensor::Light => "light",
+            Sensor::Pir => "pir",
+            Sensor::BreakBeam => "break_beam",
+            Sensor::Tilt => "tilt",
+        }
+    }
+
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Sensor::Temperature),
+            1 => Some(Sensor::Potentiometer),
+            2 => Some(Sensor::Light),
+            3 => Some(Sensor::Pir),
+            4 => Some(Sensor::BreakBeam),
+            5 => Some(Sensor::Tilt),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NackReason {
+    Unsupported,
+    BadArgument,
+}
+
+impl NackReason {
+    fn to_u8(self) -> u8 {
+        match self {
+            NackReason::Unsupported => 0,
+            NackReason::BadArgument => 1,
+        }
+    }
+
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(NackReason::Unsupported),
+            1 => Some(NackReason::BadArgument),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// Temperature is sent in hundredths of a degree, everything else raw.
+    SensorReading { sensor: Sensor, value: i32 },
+    Led { index: u8, on: bool },
+    /// `index` of 0xFF addresses every pixel.
+    Neopixel { index: u8, r: u8, g: u8, b: u8 },
+    Ack { seq: u8 },
+    Nack { seq: u8, reason: NackReason },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub seq: u8,
+    pub message: Message,
+}
+
+impl Packet {
+    pub fn new(seq: u8, message: Message) -> Self {
+        Self { seq, message }
+    }
+
+    /// Encodes the packet as a complete frame, including the trailing
+    /// delimiter, and returns the number of bytes written.
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        let mut raw: Vec<u8, MAX_RAW> = Vec::new();
+        let (kind, payload) = self.message.to_payload();
+
+        // Capacity is sized for the largest message so these can't fail.
+        let _ = raw.push(kind);
+        let _ = raw.push(self.seq);
+        let _ = raw.extend_from_slice(&payload);
+        let crc = crc16(&raw);
+        let _ = raw.extend_from_slice(&crc.to_be_bytes());
+
+        let n = cobs_encode(&raw, out)?;
+        if n >= out.len() {
+            return Err(Error::BufferTooSmall);
+        }
+        out[n] = 0;
+        Ok(n + 1)
+    }
+
+    /// Decodes a single COBS encoded frame with the delimiter stripped.
+    pub fn decode(frame: &[u8]) -> Result<Self, Error> {
+        let mut raw = [0u8; MAX_RAW];
+        let n = cobs_decode(frame, &mut raw)?;
+        if n < 4 {
+            return Err(Error::Length);
+        }
+
+        let (body, crc) = raw[..n].split_at(n - 2);
+        if crc16(body) != u16::from_be_bytes([crc[0], crc[1]]) {
+            return Err(Error::Crc);
+        }
+
+        let message = Message::from_payload(body[0], &body[2..])?;
+        Ok(Self { seq: body[1], message })
+    }
+}
+
+impl Message {
+    fn to_payload(self) -> (u8, Vec<u8, MAX_PAYLOAD>) {
+        let mut p = Vec::new();
+        let kind = match self {
+            Message::SensorReading { sensor, value } => {
+                let _ = p.push(sensor.to_u8());
+                let _ = p.extend_from_slice(&value.to_be_bytes());
+                TYPE_SENSOR
+            }
+            Message::Led { index, on } => {
+                let _ = p.extend_from_slice(&[index, on as u8]);
+                TYPE_LED
+            }
+            Message::Neopixel { index, r, g, b } => {
+                let _ = p.extend_from_slice(&[index, r, g, b]);
+                TYPE_NEOPIXEL
+            }
+            Message::Ack { seq } => {
+                let _ = p.push(seq);
+                TYPE_ACK
+            }
+            Message::Nack { seq, reason } => {
+                let _ = p.extend_from_slice(&[seq, reason.to_u8()]);
+                TYPE_NACK
+            }
+        };
+        (kind, p)
+    }
+
+    fn from_payload(kind: u8, p: &[u8]) -> Result<Self, Error> {
+        let expect = |len: usize| if p.len() == len { Ok(()) } else { Err(Error::Length) };
+
+        match kind {
+            TYPE_SENSOR => {
+                expect(5)?;
+                let sensor = Sensor::from_u8(p[0]).ok_or(Error::BadField)?;
+                let value = i32::from_be_bytes([p[1], p[2], p[3], p[4]]);
+                Ok(Message::SensorReading { sensor, value })
+            }
+            TYPE_LED => {
+                expect(2)?;
+                Ok(Message::Led { index: p[0], on: p[1] != 0 })
+            }
+            TYPE_NEOPIXEL => {
+                expect(4)?;
+                Ok(Message::Neopixel { index: p[0], r: p[1], g: p[2], b: p[3] })
+            }
+            TYPE_ACK => {
+                expect(1)?;
+                Ok(Message::Ack { seq: p[0] })
+            }
+            TYPE_NACK => {
+                expect(2)?;
+                let reason = NackReason::from_u8(p[1]).ok_or(Error::BadField)?;
+                Ok(Message::Nack { seq: p[0], reason })
+            }
+            other => Err(Error::UnknownType(other)),
+        }
+    }
+}
+
+/// Accumulates bytes from a stream and yields a packet each time a
+/// delimiter is seen.
+pub struct Decoder {
+    buf: Vec<u8, MAX_FRAME>,
+    overflow: bool,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self { buf: Vec::new(), overflow: false }
+    }
+
+    pub fn push(&mut self, byte: u8) -> Option<Result<Packet, Error>> {
+        if byte != 0 {
+            if self.buf.push(byte).is_err() {
+                self.overflow = true;
+            }
+            return None;
+        }
+
+        // Back to back delimiters are just idle line noise.
+        if self.buf.is_empty() && !self.overflow {
+            return None;
+        }
+
+        let result = if self.overflow {
+            Err(Error::FrameTooLong)
+        } else {
+            Packet::decode(&self.buf)
+        };
+        self.buf.clear();
+        self.overflow = false;
+        Some(result)
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CRC-16/CCITT-FALSE (poly 0x1021, init 0xFFFF).
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+pub fn cobs_encode(input: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut code_idx = 0;
+    let mut out_idx = 1;
+    let mut code: u8 = 1;
+
+    for &byte in input {
+        if byte == 0 {
+            *out.get_mut(code_idx).ok_or(Error::BufferTooSmall)? = code;
+            code_idx = out_idx;
+            out_idx += 1;
+            code = 1;
+        } else {
+            *out.get_mut(out_idx).ok_or(Error::BufferTooSmall)? = byte;
+            out_idx += 1;
+            code += 1;
+            if code == 0xFF {
+                *out.get_mut(code_idx).ok_or(Error::BufferTooSmall)? = code;
+                code_idx = out_idx;
+                out_idx += 1;
+                code = 1;
+            }
+        }
+    }
+
+    *out.get_mut(code_idx).ok_or(Error::BufferTooSmall)? = code;
+    Ok(out_idx)
+}
+
+pub fn cobs_decode(input: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut in_idx = 0;
+    let mut out_idx = 0;
+
+    while in_idx < input.len() {
+        let code = input[in_idx];
+        if code == 0 {
+            return Err(Error::Cobs);
+        }
+        in_idx += 1;
+
+        for _ in 1..code {
+            let byte = *input.get(in_idx).ok_or(Error::Cobs)?;
+            if byte == 0 {
+                return Err(Error::Cobs);
+            }
+            *out.get_mut(out_idx).ok_or(Error::BufferTooSmall)? = byte;
+            in_idx += 1;
+            out_idx += 1;
+        }
+
+        if code != 0xFF && in_idx < input.len() {
+            *out.get_mut(out_idx).ok_or(Error::BufferTooSmall)? = 0;
+            out_idx += 1;
+        }
+    }
+
+    Ok(out_idx)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    const MESSAGES: [Message; 6] = [
+        Message::SensorReading { sensor: Sensor::Temperature, value: -1234 },
+        Message::SensorReading { sensor: Sensor::Tilt, value: i32::MAX },
+        Message::Led { index: 2, on: true },
+        Message::Neopixel { index: 0xFF, r: 0, g: 128, b: 255 },
+        Message::Ack { seq: 0 },
+        Message::Nack { seq: 7, reason: NackReason::BadArgument },
+    ];
+
+    fn frame(packet: Packet) -> Vec<u8, MAX_FRAME> {
+        let mut out = [0u8; MAX_FRAME];
+        let n = packet.encode(&mut out).unwrap();
+        Vec::from_slice(&out[..n]).unwrap()
+    }
+
+    fn feed(decoder: &mut Decoder, bytes: &[u8]) -> std::vec::Vec<Result<Packet, Error>> {
+        bytes.iter().filter_map(|&byte| decoder.push(byte)).collect()
+    }
+
+    // A frame with its contents sent raw, skipping the encoder's checks.
+    fn raw_frame(raw: &[u8]) -> std::vec::Vec<u8> {
+        let mut out = [0u8; 300];
+        let n = cobs_encode(raw, &mut out).unwrap();
+        let mut frame = out[..n].to_vec();
+        frame.push(0);
+        frame
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        let mut decoder = Decoder::new();
+        for (seq, message) in MESSAGES.into_iter().enumerate() {
+            let packet = Packet::new(seq as u8, message);
+            let frame = frame(packet);
+            assert_eq!(frame.iter().position(|&b| b == 0), Some(frame.len() - 1));
+            assert_eq!(feed(&mut decoder, &frame), [Ok(packet)]);
+        }
+    }
+
+    #[test]
+    fn bit_flips_fail_the_crc() {
+        let frame = frame(Packet::new(3, Message::Neopixel { index: 1, r: 2, g: 3, b: 4 }));
+        // Everything but the COBS code byte and the delimiter, so the
+        // framing itself stays intact.
+        for i in 1..frame.len() - 1 {
+            for bit in 0..8 {
+                let mut bad = frame.clone();
+                bad[i] ^= 1 << bit;
+                if bad[i] == 0 {
+                    continue;
+                }
+                assert_eq!(Packet::decode(&bad[..bad.len() - 1]), Err(Error::Crc), "byte {i} bit {bit}");
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        for message in MESSAGES {
+            let frame = frame(Packet::new(9, message));
+            for len in 1..frame.len() - 1 {
+                let result = Packet::decode(&frame[..len]);
+                assert!(matches!(result, Err(Error::Length | Error::Cobs)), "{message:?} cut to {len}: {result:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_bad_fields() {
+        let mut decoder = Decoder::new();
+        let sensor = raw_frame(&with_crc(&[TYPE_SENSOR, 1, 99, 0, 0, 0, 1]));
+        assert_eq!(feed(&mut decoder, &sensor), [Err(Error::BadField)]);
+        let nack = raw_frame(&with_crc(&[TYPE_NACK, 1, 1, 99]));
+        assert_eq!(feed(&mut decoder, &nack), [Err(Error::BadField)]);
+        let kind = raw_frame(&with_crc(&[0x7F, 1]));
+        assert_eq!(feed(&mut decoder, &kind), [Err(Error::UnknownType(0x7F))]);
+        let short = raw_frame(&with_crc(&[TYPE_LED, 1, 2]));
+        assert_eq!(feed(&mut decoder, &short), [Err(Error::Length)]);
+    }
+
+    fn with_crc(body: &[u8]) -> std::vec::Vec<u8> {
+        let mut raw = body.to_vec();
+        raw.extend_from_slice(&crc16(body).to_be_bytes());
+        raw
+    }
+
+    #[test]
+    fn oversized_frames_are_too_long() {
+        let mut decoder = Decoder::new();
+        let results = feed(&mut decoder, &[0x55; MAX_FRAME * 3]);
+        assert!(results.is_empty());
+        assert_eq!(decoder.push(0), Some(Err(Error::FrameTooLong)));
+    }
+
+    #[test]
+    fn decoder_resyncs_on_the_next_delimiter() {
+        let mut decoder = Decoder::new();
+        let good = frame(Packet::new(1, Message::Led { index: 0, on: false }));
+
+        // Joining a stream halfway through a frame.
+        let mut stream = good[3..].to_vec();
+        stream.extend_from_slice(&good);
+        // Garbage, idle delimiters, then another good frame.
+        stream.extend_from_slice(&[0x12, 0x34, 0x56, 0, 0, 0]);
+        stream.extend_from_slice(&good);
+
+        let results = feed(&mut decoder, &stream);
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_err());
+        assert_eq!(results[1], Ok(Packet::new(1, Message::Led { index: 0, on: false })));
+        assert!(results[2].is_err());
+        assert_eq!(results[3], results[1]);
+    }
+
+    #[test]
+    fn crc_matches_the_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn cobs_handles_long_runs_of_nonzero_bytes() {
+        for len in [253, 254, 255, 300, 600] {
+            let input: std::vec::Vec<u8> = (0..len).map(|i| (i % 255) as u8 + 1).collect();
+            let mut encoded = [0u8; 700];
+            let n = cobs_encode(&input, &mut encoded).unwrap();
+            assert!(!encoded[..n].contains(&0), "len {len}");
+            assert_eq!(n, len + 1 + len / 254);
+
+            let mut decoded = [0u8; 700];
+            let m = cobs_decode(&encoded[..n], &mut decoded).unwrap();
+            assert_eq!(&decoded[..m], &input[..], "len {len}");
+        }
+    }
+
+    #[test]
+    fn cobs_keeps_zeros() {
+        for input in [&[0u8][..], &[0, 0], &[1, 0, 2, 0], &[0, 1, 2, 3, 0]] {
+            let mut encoded = [0u8; 16];
+            let n = cobs_encode(input, &mut encoded).unwrap();
+            assert!(!encoded[..n].contains(&0));
+            let mut decoded = [0u8; 16];
+            let m = cobs_decode(&encoded[..n], &mut decoded).unwrap();
+            assert_eq!(&decoded[..m], input);
+        }
+    }
+}
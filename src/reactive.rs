@@ -0,0 +1,123 @@
+// LED effects that follow the sensors.
+//
+// A `Binding` ties a sensor to one of the `Animator`'s parameters through a
+// `Curve`, a table of (reading, level) points joined by straight lines, so
+// a new mapping is just data: a knob to hue, ambient light to brightness,
+// temperature to a colour from blue to red. `Reactive` applies a set of
+// bindings each frame, smoothing the levels so ADC noise doesn't make the
+// strip flicker.
+
+use crate::color::Hsv;
+use crate::dashboard::Readings;
+use crate::effects::Animator;
+
+/// The most bindings a `Reactive` can hold.
+pub const MAX_BINDINGS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Percent.
+    Light,
+    /// Percent.
+    Potentiometer,
+    /// Tenths of a degree C, nothing until there's been a reading.
+    Temperature,
+}
+
+impl Source {
+    pub fn read(self, readings: &Readings, temperature: Option<f32>) -> Option<i32> {
+        match self {
+            Source::Light => Some(readings.light as i32),
+            Source::Potentiometer => Some(readings.potentiometer as i32),
+            Source::Temperature => temperature.map(|t| (t * 10.0) as i32),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+    /// Colour round the wheel, for effects that have a colour.
+    Hue,
+    Brightness,
+    /// Percent of normal speed.
+    Speed,
+}
+
+/// Points of (reading, level) in increasing order of reading. Readings
+/// before the first point or after the last get its level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Curve(pub &'static [(i32, u8)]);
+
+impl Curve {
+    pub fn map(&self, reading: i32) -> u8 {
+        let points = self.0;
+        let Some(&(first, level)) = points.first() else {
+            return 0;
+        };
+        if reading <= first {
+            return level;
+        }
+        for pair in points.windows(2) {
+            let [(x0, y0), (x1, y1)] = [pair[0], pair[1]];
+            if reading <= x1 {
+                let t = (reading - x0) * 256 / (x1 - x0).max(1);
+                return (y0 as i32 + (y1 as i32 - y0 as i32) * t / 256) as u8;
+            }
+        }
+        points[points.len() - 1].1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub source: Source,
+    pub param: Param,
+    pub curve: Curve,
+}
+
+pub struct Reactive {
+    bindings: &'static [Binding],
+    // Smoothed levels in 256ths, `None` until the source has a reading.
+    levels: [Option<i32>; MAX_BINDINGS],
+}
+
+impl Reactive {
+    /// Panics if there are more than `MAX_BINDINGS`.
+    pub fn new(bindings: &'static [Binding]) -> Self {
+        assert!(bindings.len() <= MAX_BINDINGS, "too many bindings");
+        Self { bindings, levels: [None; MAX_BINDINGS] }
+    }
+
+    /// The smoothed level each binding is at, in the same order.
+    pub fn levels(&self) -> impl Iterator<Item = Option<u8>> + '_ {
+        self.levels[..self.bindings.len()].iter().map(|level| level.map(|l| (l >> 8) as u8))
+    }
+
+    /// Takes in new readings and sets the animator's parameters from them.
+    /// Parameters whose source hasn't read anything yet are left alone.
+    pub fn update(&mut self, readings: &Readings, temperature: Option<f32>, animator: &mut Animator) {
+        for (binding, level) in self.bindings.iter().zip(&mut self.levels) {
+            let Some(reading) = binding.source.read(readings, temperature) else {
+                continue;
+            };
+            let target = (binding.curve.map(reading) as i32) << 8;
+            // A quarter of the way there each time, the first reading
+            // goes straight in.
+            let smoothed = match *level {
+                Some(old) if (target - old) / 4 != 0 => old + (target - old) / 4,
+                _ => target,
+            };
+            *level = Some(smoothed);
+
+            let value = (smoothed >> 8) as u8;
+            match binding.param {
+                Param::Hue => {
+                    let effect = animator.effect().with_color(Hsv::new(value, 255, 255).into());
+                    animator.replace_effect(effect);
+                }
+                Param::Brightness => animator.set_brightness(value),
+                Param::Speed => animator.set_speed(value as u16),
+            }
+        }
+    }
+}
@@ -0,0 +1,333 @@
+// Running several projects side by side on one core.
+//
+// Each project's work is cut into short steps that don't block, and the
+// `Scheduler` says which one is due next: blink the day2 LEDs every 500 ms,
+// start a DS18B20 conversion every 2 s and read it back 800 ms after that,
+// and so on. It doesn't hold the tasks themselves, just their timing, so
+// the main loop matches on the `TaskId` it hands back and every task can
+// borrow whatever it needs from `main` without it all being shared
+// through statics. Anything that has to react faster than a task step,
+// like the USB stack, stays in its interrupt.
+//
+// Time comes from a `Clock`, the RP2040 timer on the board or a
+// `VirtualClock` that's moved on by hand to check the timing on the host.
+
+use heapless::Vec;
+use rp_pico::hal::Timer;
+
+/// Something that tells the time in µs.
+pub trait Clock {
+    fn now_us(&mut self) -> u64;
+}
+
+impl Clock for Timer {
+    fn now_us(&mut self) -> u64 {
+        self.get_counter().ticks()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VirtualClock {
+    now_us: u64,
+}
+
+impl VirtualClock {
+    pub fn new(now_us: u64) -> Self {
+        Self { now_us }
+    }
+
+    pub fn advance(&mut self, us: u64) {
+        self.now_us += us;
+    }
+
+    pub fn set(&mut self, now_us: u64) {
+        self.now_us = now_us;
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now_us(&mut self) -> u64 {
+        self.now_us
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(pub usize);
+
+#[derive(Debug, Clone, Copy)]
+struct Task {
+    period_us: u64,
+    // `None` while paused.
+    due_us: Option<u64>,
+    runs: u32,
+    skipped: u32,
+    worst_late_us: u64,
+}
+
+/// How a task has kept to time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskStats {
+    pub runs: u32,
+    /// Periods dropped because it was more than a whole one behind.
+    pub skipped: u32,
+    /// Longest it's been started after its deadline.
+    pub worst_late_us: u64,
+}
+
+/// Timing for up to `N` periodic tasks.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler<const N: usize> {
+    tasks: Vec<Task, N>,
+}
+
+impl<const N: usize> Scheduler<N> {
+    pub fn new() -> Self {
+        Self { tasks: Vec::new() }
+    }
+
+    /// Adds a task due first at `first_us` and every `period_us` after
+    /// that. A period of 0 makes it a one-off, paused once it's run.
+    /// Panics if there are already `N` tasks.
+    pub fn every(&mut self, period_us: u64, first_us: u64) -> TaskId {
+        let task = Task { period_us, due_us: Some(first_us), runs: 0, skipped: 0, worst_late_us: 0 };
+        if self.tasks.push(task).is_err() {
+            panic!("too many tasks");
+        }
+        TaskId(self.tasks.len() - 1)
+    }
+
+    /// Stops a task coming due until it's resumed.
+    pub fn pause(&mut self, id: TaskId) {
+        if let Some(task) = self.tasks.get_mut(id.0) {
+            task.due_us = None;
+        }
+    }
+
+    /// Makes a task due next at `at_us`, whether it was paused or not.
+    pub fn resume(&mut self, id: TaskId, at_us: u64) {
+        if let Some(task) = self.tasks.get_mut(id.0) {
+            task.due_us = Some(at_us);
+        }
+    }
+
+    pub fn set_period(&mut self, id: TaskId, period_us: u64) {
+        if let Some(task) = self.tasks.get_mut(id.0) {
+            task.period_us = period_us;
+        }
+    }
+
+    pub fn stats(&self, id: TaskId) -> Option<TaskStats> {
+        let task = self.tasks.get(id.0)?;
+        Some(TaskStats { runs: task.runs, skipped: task.skipped, worst_late_us: task.worst_late_us })
+    }
+
+    /// When the next task is due, or `None` if they're all paused.
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.tasks.iter().filter_map(|task| task.due_us).min()
+    }
+
+    /// How long there is to wait at `now_us` before anything's due, 0 if
+    /// something already is.
+    pub fn idle_us(&self, now_us: u64) -> Option<u64> {
+        self.next_deadline().map(|due| due.saturating_sub(now_us))
+    }
+
+    /// Takes the task that's been due longest at `now_us`, the first added
+    /// if more than one has the same deadline, and sets when it's due next.
+    /// Call until it returns `None` to run everything that's due.
+    ///
+    /// A task keeps to its own beat, so one started late is still next due
+    /// a period after its deadline rather than after `now_us`. If that's
+    /// already gone by as well, the periods it missed are skipped rather
+    /// than run back to back.
+    pub fn next_due(&mut self, now_us: u64) -> Option<TaskId> {
+        let (index, due_us) = self
+            .tasks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, task)| Some((index, task.due_us?)))
+            .filter(|&(_, due_us)| due_us <= now_us)
+            .min_by_key(|&(_, due_us)| due_us)?;
+
+        let task = &mut self.tasks[index];
+        task.runs = task.runs.wrapping_add(1);
+        task.worst_late_us = task.worst_late_us.max(now_us - due_us);
+        task.due_us = match task.period_us {
+            0 => None,
+            period_us => {
+                let behind = (now_us - due_us) / period_us;
+                task.skipped = task.skipped.saturating_add(behind as u32);
+                Some(due_us + (behind + 1) * period_us)
+            }
+        };
+        Some(TaskId(index))
+    }
+
+    /// `next_due` at the clock's time.
+    pub fn poll(&mut self, clock: &mut impl Clock) -> Option<TaskId> {
+        let now_us = clock.now_us();
+        self.next_due(now_us)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    // Everything due at the clock's time, in the order it comes.
+    fn due<const N: usize>(scheduler: &mut Scheduler<N>, clock: &mut VirtualClock) -> Vec<usize> {
+        core::iter::from_fn(|| scheduler.poll(clock)).map(|id| id.0).collect()
+    }
+
+    #[test]
+    fn equal_deadlines_go_in_the_order_added() {
+        let mut scheduler: Scheduler<4> = Scheduler::new();
+        let mut clock = VirtualClock::new(0);
+        scheduler.every(1_000, 1_000);
+        scheduler.every(500, 1_000);
+        scheduler.every(2_000, 1_000);
+
+        assert_eq!(due(&mut scheduler, &mut clock), []);
+        clock.set(1_000);
+        assert_eq!(due(&mut scheduler, &mut clock), [0, 1, 2]);
+        clock.set(1_500);
+        assert_eq!(due(&mut scheduler, &mut clock), [1]);
+        clock.set(2_000);
+        assert_eq!(due(&mut scheduler, &mut clock), [0, 1]);
+    }
+
+    #[test]
+    fn longest_overdue_goes_first() {
+        let mut scheduler: Scheduler<4> = Scheduler::new();
+        let mut clock = VirtualClock::new(0);
+        scheduler.every(1_000, 300);
+        scheduler.every(1_000, 100);
+        scheduler.every(1_000, 200);
+
+        clock.set(300);
+        assert_eq!(due(&mut scheduler, &mut clock), [1, 2, 0]);
+    }
+
+    #[test]
+    fn late_tasks_keep_to_their_beat() {
+        let mut scheduler: Scheduler<2> = Scheduler::new();
+        let mut clock = VirtualClock::new(1_300);
+        let task = scheduler.every(1_000, 1_000);
+
+        assert_eq!(due(&mut scheduler, &mut clock), [0]);
+        // Next due a period after its deadline, not after it ran.
+        assert_eq!(scheduler.next_deadline(), Some(2_000));
+        assert_eq!(scheduler.stats(task), Some(TaskStats { runs: 1, skipped: 0, worst_late_us: 300 }));
+    }
+
+    #[test]
+    fn missed_periods_are_skipped() {
+        let mut scheduler: Scheduler<2> = Scheduler::new();
+        let mut clock = VirtualClock::new(0);
+        let task = scheduler.every(500, 500);
+
+        // 1.75 s late, three whole periods behind.
+        clock.set(2_250);
+        assert_eq!(due(&mut scheduler, &mut clock), [0]);
+        assert_eq!(scheduler.next_deadline(), Some(2_500));
+        assert_eq!(scheduler.stats(task), Some(TaskStats { runs: 1, skipped: 3, worst_late_us: 1_750 }));
+
+        // Back on time, the counts stay.
+        clock.set(2_500);
+        assert_eq!(due(&mut scheduler, &mut clock), [0]);
+        assert_eq!(scheduler.stats(task), Some(TaskStats { runs: 2, skipped: 3, worst_late_us: 1_750 }));
+    }
+
+    #[test]
+    fn worst_late_is_the_worst_so_far() {
+        let mut scheduler: Scheduler<2> = Scheduler::new();
+        let mut clock = VirtualClock::new(0);
+        let task = scheduler.every(1_000, 0);
+
+        for late in [10, 250, 40] {
+            let deadline = scheduler.next_deadline().unwrap();
+            clock.set(deadline + late);
+            assert_eq!(due(&mut scheduler, &mut clock), [0]);
+        }
+        let stats = scheduler.stats(task).unwrap();
+        assert_eq!((stats.runs, stats.worst_late_us), (3, 250));
+    }
+
+    #[test]
+    fn one_off_tasks_run_once() {
+        let mut scheduler: Scheduler<2> = Scheduler::new();
+        let mut clock = VirtualClock::new(0);
+        let blink = scheduler.every(1_000, 1_000);
+        let once = scheduler.every(0, 800);
+
+        clock.set(1_000);
+        assert_eq!(due(&mut scheduler, &mut clock), [1, 0]);
+        clock.set(5_000);
+        assert_eq!(due(&mut scheduler, &mut clock), [0]);
+        assert_eq!(scheduler.stats(once).map(|stats| stats.runs), Some(1));
+        assert_eq!(scheduler.stats(blink).map(|stats| stats.skipped), Some(3));
+
+        // Until it's resumed.
+        scheduler.resume(once, 5_100);
+        clock.set(5_100);
+        assert_eq!(due(&mut scheduler, &mut clock), [1]);
+        assert_eq!(scheduler.stats(once).map(|stats| stats.runs), Some(2));
+    }
+
+    #[test]
+    fn paused_tasks_wait_for_resume() {
+        let mut scheduler: Scheduler<2> = Scheduler::new();
+        let mut clock = VirtualClock::new(0);
+        let task = scheduler.every(100, 100);
+
+        scheduler.pause(task);
+        assert_eq!(scheduler.next_deadline(), None);
+        clock.set(1_000);
+        assert_eq!(due(&mut scheduler, &mut clock), []);
+
+        // It picks up from wherever it's resumed, nothing counted as
+        // missed while it was paused.
+        scheduler.resume(task, 1_050);
+        assert_eq!(due(&mut scheduler, &mut clock), []);
+        clock.set(1_050);
+        assert_eq!(due(&mut scheduler, &mut clock), [0]);
+        assert_eq!(scheduler.next_deadline(), Some(1_150));
+        assert_eq!(scheduler.stats(task), Some(TaskStats { runs: 1, skipped: 0, worst_late_us: 0 }));
+    }
+
+    #[test]
+    fn idle_until_the_next_deadline() {
+        let mut scheduler: Scheduler<2> = Scheduler::new();
+        assert_eq!(scheduler.idle_us(0), None);
+
+        let slow = scheduler.every(2_000, 2_000);
+        scheduler.every(500, 500);
+        assert_eq!(scheduler.idle_us(0), Some(500));
+        assert_eq!(scheduler.idle_us(400), Some(100));
+        assert_eq!(scheduler.idle_us(500), Some(0));
+        // Overdue is still 0.
+        assert_eq!(scheduler.idle_us(700), Some(0));
+
+        assert_eq!(scheduler.next_due(700), Some(TaskId(1)));
+        assert_eq!(scheduler.idle_us(700), Some(300));
+
+        scheduler.pause(TaskId(1));
+        assert_eq!(scheduler.idle_us(700), Some(1_300));
+        scheduler.pause(slow);
+        assert_eq!(scheduler.idle_us(700), None);
+    }
+
+    #[test]
+    fn unknown_tasks_are_ignored() {
+        let mut scheduler: Scheduler<2> = Scheduler::new();
+        scheduler.every(100, 100);
+        scheduler.pause(TaskId(5));
+        scheduler.resume(TaskId(5), 0);
+        assert_eq!(scheduler.stats(TaskId(5)), None);
+        assert_eq!(scheduler.next_deadline(), Some(100));
+    }
+}
@@ -0,0 +1,47 @@
+// Screens shared between the firmware and the host renderer, so what gets
+// checked on the host is exactly what the panel shows.
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Alignment;
+
+use crate::display::{Flush, FontSize, Icon, Screen};
+
+/// Title bar for day11, drawn once.
+pub fn day11_title<D: Flush>(screen: &mut Screen<D>) -> Result<(), D::Error> {
+    let width = screen.size().width;
+    screen.clear()?;
+    screen.icon(Icon::Usb, Point::new(0, 0))?;
+    screen.text("Codemas", Point::new(12, 0), FontSize::Small, Alignment::Left)?;
+    screen.line(Point::new(0, 10), Point::new(width as i32 - 1, 10), 1)?;
+    Ok(())
+}
+
+/// The day11 gauge, `level` is a percentage.
+pub fn day11_gauge<D: Flush>(screen: &mut Screen<D>, level: u32) -> Result<(), D::Error> {
+    let width = screen.size().width;
+    let gauge = Rectangle::new(Point::new(0, 14), Size::new(width, 10));
+    screen.gauge(gauge, level as f32 / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::{FrameBuffer128x32, FrameBuffer128x64};
+
+    #[test]
+    fn day11_128x32() {
+        let mut screen = Screen::new(FrameBuffer128x32::new());
+        day11_title(&mut screen).unwrap();
+        day11_gauge(&mut screen, 42).unwrap();
+        screen.target().assert_golden(include_str!("golden/day11_128x32.txt"));
+    }
+
+    #[test]
+    fn day11_128x64() {
+        let mut screen = Screen::new(FrameBuffer128x64::new());
+        day11_title(&mut screen).unwrap();
+        day11_gauge(&mut screen, 42).unwrap();
+        screen.target().assert_golden(include_str!("golden/day11_128x64.txt"));
+    }
+}
@@ -0,0 +1,93 @@
+// What the LED strip drivers have in common, so effects and projects can
+// drive any kind of strip the same way.
+//
+// Each driver keeps the colours asked for in a `Frame` and only applies
+// brightness, gamma correction and the power limit as it sends them, so
+// the pixels always read back as they were set.
+
+use rgb::RGB8;
+
+use crate::color::{self, PowerLimit};
+
+pub struct Frame<const N: usize> {
+    pub pixels: [RGB8; N],
+    pub brightness: u8,
+    /// Off by default.
+    pub gamma: bool,
+    pub power_limit: Option<PowerLimit>,
+}
+
+impl<const N: usize> Frame<N> {
+    pub const fn new() -> Self {
+        Self {
+            pixels: [color::BLACK; N],
+            brightness: 255,
+            gamma: false,
+            power_limit: None,
+        }
+    }
+
+    /// The pixels as they should go out to the strip.
+    pub fn corrected(&self) -> [RGB8; N] {
+        let mut frame = self.pixels.map(|pixel| color::scale(pixel, self.brightness));
+        if self.gamma {
+            frame = frame.map(color::gamma);
+        }
+        if let Some(limit) = self.power_limit {
+            limit.limit(&mut frame);
+        }
+        frame
+    }
+}
+
+impl<const N: usize> Default for Frame<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A strip of `N` addressable LEDs.
+pub trait Strip<const N: usize> {
+    type Error;
+
+    fn frame(&self) -> &Frame<N>;
+
+    fn frame_mut(&mut self) -> &mut Frame<N>;
+
+    /// Sends the pixels to the strip.
+    fn write(&mut self) -> Result<(), Self::Error>;
+
+    fn pixels(&self) -> &[RGB8; N] {
+        &self.frame().pixels
+    }
+
+    /// Changes only show up on the strip after `write`.
+    fn pixels_mut(&mut self) -> &mut [RGB8; N] {
+        &mut self.frame_mut().pixels
+    }
+
+    /// Sets every pixel, doesn't write.
+    fn fill(&mut self, color: RGB8) {
+        *self.pixels_mut() = [color; N];
+    }
+
+    fn set_brightness(&mut self, brightness: u8) {
+        self.frame_mut().brightness = brightness;
+    }
+
+    /// Gamma correct on the way out.
+    fn set_gamma(&mut self, gamma: bool) {
+        self.frame_mut().gamma = gamma;
+    }
+
+    /// Dims whole frames that would draw more than the limit.
+    fn set_power_limit(&mut self, limit: Option<PowerLimit>) {
+        self.frame_mut().power_limit = limit;
+    }
+
+    /// Turns every pixel off.
+    fn clear(&mut self) -> Result<(), Self::Error> {
+        self.fill(color::BLACK);
+        self.write()
+    }
+}
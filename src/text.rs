@@ -0,0 +1,308 @@
+// Laying text out on the OLED: word wrapping and a scrolling log.
+//
+// Everything here counts in character columns, which works because all our
+// fonts are monospaced; `FontSize::columns` turns a width in pixels into
+// columns. Nothing in this module draws pixels itself except `Log::draw`,
+// so line breaking can be checked on the host without a screen.
+//
+// Words are broken at spaces. A word longer than a whole line is split
+// wherever the line runs out. Spaces at a wrap are dropped, so wrapped
+// lines never start or end with one.
+
+use core::fmt;
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Alignment;
+use heapless::{Deque, String};
+
+use crate::display::{Flush, FontSize, Screen};
+
+/// Splits `text` into lines no more than `columns` characters long. Each
+/// `\n` starts a new line, like `str::lines` a trailing one doesn't.
+pub fn wrap(text: &str, columns: usize) -> Wrap<'_> {
+    let rest = Some(text).filter(|text| !text.is_empty());
+    Wrap { rest, columns: columns.max(1) }
+}
+
+pub struct Wrap<'a> {
+    rest: Option<&'a str>,
+    columns: usize,
+}
+
+impl<'a> Iterator for Wrap<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest?;
+        let (line, after) = match rest.find('\n') {
+            Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+            None => (rest, None),
+        };
+
+        // Byte index of the first character that doesn't fit.
+        let Some((limit, _)) = line.char_indices().nth(self.columns) else {
+            self.rest = after.filter(|after| !after.is_empty());
+            return Some(line.trim_end_matches(' '));
+        };
+
+        let head = &line[..limit];
+        let split = if line[limit..].starts_with(' ') {
+            limit
+        } else {
+            match head.rfind(' ') {
+                Some(i) if !head[..i].trim_end_matches(' ').is_empty() => i,
+                _ => limit,
+            }
+        };
+
+        // Drop the spaces the line was broken at, and the newline too if
+        // nothing but spaces were left before it.
+        let rest = rest[split..].trim_start_matches(' ');
+        let rest = rest.strip_prefix('\n').unwrap_or(rest);
+        self.rest = Some(rest).filter(|rest| !rest.is_empty());
+        Some(line[..split].trim_end_matches(' '))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineState {
+    /// The last line ended with a newline, or there are no lines yet.
+    Closed,
+    /// More text is added to the end of the last line.
+    Open,
+    /// The last line was wrapped, spaces are dropped until the next word.
+    Wrapped,
+}
+
+/// The last `LINES` lines of text written to it, wrapped at `COLUMNS`
+/// characters. Expects ASCII, as that's all the fonts have. Implements
+/// `fmt::Write`, so `write!` and `writeln!` work as they would on a
+/// terminal, one character at a time.
+pub struct Log<const LINES: usize, const COLUMNS: usize> {
+    lines: Deque<String<COLUMNS>, LINES>,
+    state: LineState,
+}
+
+impl<const LINES: usize, const COLUMNS: usize> Log<LINES, COLUMNS> {
+    pub const fn new() -> Self {
+        Self {
+            lines: Deque::new(),
+            state: LineState::Closed,
+        }
+    }
+
+    /// Adds `text` and ends the line.
+    pub fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            self.push_char(c);
+        }
+        self.push_char('\n');
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.state = LineState::Closed;
+    }
+
+    /// Oldest first.
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        self.lines.iter().map(String::as_str)
+    }
+
+    fn new_line(&mut self) -> &mut String<COLUMNS> {
+        if self.lines.is_full() {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(String::new()).ok();
+        self.lines.back_mut().unwrap()
+    }
+
+    fn push_char(&mut self, c: char) {
+        if COLUMNS == 0 || LINES == 0 {
+            return;
+        }
+        match (c, self.state) {
+            ('\n', LineState::Closed) => {
+                self.new_line();
+            }
+            ('\n', _) => self.state = LineState::Closed,
+            (' ', LineState::Wrapped) => {}
+            (_, LineState::Closed | LineState::Wrapped) => {
+                self.new_line().push(c).ok();
+                self.state = LineState::Open;
+            }
+            (_, LineState::Open) => {
+                let line = self.lines.back_mut().unwrap();
+                if line.push(c).is_ok() {
+                    return;
+                }
+                if c == ' ' {
+                    self.state = LineState::Wrapped;
+                    return;
+                }
+                // Carry the word that didn't fit over to a new line, unless
+                // it fills the whole line anyway.
+                let mut word: String<COLUMNS> = String::new();
+                if let Some(i) = line.rfind(' ') {
+                    let keep = line[..i].trim_end_matches(' ').len();
+                    if keep > 0 {
+                        word.push_str(&line[i + 1..]).ok();
+                        line.truncate(keep);
+                    }
+                }
+                let next = self.new_line();
+                next.push_str(&word).ok();
+                next.push(c).ok();
+            }
+        }
+    }
+
+    /// Draws the newest lines that fit in `area`, oldest at the top. Blanks
+    /// `area` first.
+    pub fn draw<D: Flush>(&self, screen: &mut Screen<D>, area: Rectangle, size: FontSize) -> Result<(), D::Error> {
+        screen.clear_region(area)?;
+        let line_height = size.char_size().height;
+        let rows = size.rows(area.size.height);
+        let skip = self.lines.len().saturating_sub(rows);
+        for (i, line) in self.lines().skip(skip).enumerate() {
+            let position = area.top_left + Point::new(0, (i as u32 * line_height) as i32);
+            screen.text(line, position, size, Alignment::Left)?;
+        }
+        Ok(())
+    }
+}
+
+impl<const LINES: usize, const COLUMNS: usize> Default for Log<LINES, COLUMNS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const LINES: usize, const COLUMNS: usize> fmt::Write for Log<LINES, COLUMNS> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.push_char(c);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::fmt::Write;
+    use std::vec::Vec;
+
+    fn lines(text: &str, columns: usize) -> Vec<&str> {
+        wrap(text, columns).collect()
+    }
+
+    fn log_lines<const L: usize, const C: usize>(log: &Log<L, C>) -> Vec<&str> {
+        log.lines().collect()
+    }
+
+    #[test]
+    fn short_text_is_one_line() {
+        assert_eq!(lines("hello", 10), ["hello"]);
+        assert_eq!(lines("", 10), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn breaks_at_the_last_space_that_fits() {
+        assert_eq!(lines("the quick brown fox", 10), ["the quick", "brown fox"]);
+        assert_eq!(lines("the quick brown fox", 9), ["the quick", "brown fox"]);
+        assert_eq!(lines("the quick brown fox", 5), ["the", "quick", "brown", "fox"]);
+    }
+
+    #[test]
+    fn long_words_are_split_where_the_line_runs_out() {
+        assert_eq!(lines("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(lines("to abcdefghij", 4), ["to", "abcd", "efgh", "ij"]);
+        assert_eq!(lines("abcdefghij ok", 4), ["abcd", "efgh", "ij", "ok"]);
+    }
+
+    #[test]
+    fn spaces_at_the_wrap_are_dropped() {
+        assert_eq!(lines("abcd    efgh", 4), ["abcd", "efgh"]);
+        assert_eq!(lines("ab    cd ef", 5), ["ab", "cd ef"]);
+        assert_eq!(lines("abc  de", 4), ["abc", "de"]);
+        // Spaces that fit on a line without wrapping are kept.
+        assert_eq!(lines("a  b", 10), ["a  b"]);
+        // Trailing ones aren't.
+        assert_eq!(lines("ab   ", 10), ["ab"]);
+    }
+
+    #[test]
+    fn newlines() {
+        assert_eq!(lines("one\ntwo", 10), ["one", "two"]);
+        assert_eq!(lines("one\n\ntwo", 10), ["one", "", "two"]);
+        assert_eq!(lines("one\n", 10), ["one"]);
+        assert_eq!(lines("one\n\n", 10), ["one", ""]);
+        assert_eq!(lines("\none", 10), ["", "one"]);
+        // A wrap right before a newline doesn't leave an empty line too.
+        assert_eq!(lines("abcd \nefgh", 4), ["abcd", "efgh"]);
+        assert_eq!(lines("abcd\nefgh", 4), ["abcd", "efgh"]);
+    }
+
+    #[test]
+    fn wrap_matches_str_lines_when_nothing_is_too_long() {
+        for text in ["a\nb", "a\n", "a\n\n", "\n", "\n\n", "a\n\nb\n"] {
+            assert_eq!(lines(text, 10), text.lines().collect::<Vec<_>>(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn log_keeps_lines_as_written() {
+        let mut log: Log<4, 10> = Log::new();
+        log.push("one");
+        write!(log, "two ").unwrap();
+        writeln!(log, "halves").unwrap();
+        log.push("");
+        assert_eq!(log_lines(&log), ["one", "two halves", ""]);
+    }
+
+    #[test]
+    fn log_carries_the_last_word_to_a_new_line() {
+        let mut log: Log<4, 10> = Log::new();
+        log.push("the quick brown fox");
+        assert_eq!(log_lines(&log), ["the quick", "brown fox"]);
+
+        let mut log: Log<4, 4> = Log::new();
+        log.push("abcdefghij ok");
+        assert_eq!(log_lines(&log), ["abcd", "efgh", "ij", "ok"]);
+    }
+
+    #[test]
+    fn log_drops_spaces_at_the_wrap() {
+        let mut log: Log<4, 4> = Log::new();
+        log.push("abcd    efgh");
+        assert_eq!(log_lines(&log), ["abcd", "efgh"]);
+    }
+
+    #[test]
+    fn log_matches_wrap() {
+        let text = "Hello from the Pico, here is a rather long line\nand a short one";
+        let mut log: Log<16, 12> = Log::new();
+        log.push(text);
+        assert_eq!(log_lines(&log), lines(text, 12));
+    }
+
+    #[test]
+    fn log_drops_the_oldest_lines_when_full() {
+        let mut log: Log<3, 8> = Log::new();
+        for i in 0..5 {
+            writeln!(log, "line {i}").unwrap();
+        }
+        assert_eq!(log_lines(&log), ["line 2", "line 3", "line 4"]);
+
+        // Wrapping counts too.
+        log.push("one two three");
+        assert_eq!(log_lines(&log), ["line 4", "one two", "three"]);
+
+        log.clear();
+        assert_eq!(log.lines().len(), 0);
+    }
+}
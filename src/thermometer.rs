@@ -0,0 +1,83 @@
+// The DS18B20 on its one wire bus, read without blocking for the
+// conversion.
+//
+// A 12 bit conversion takes 750 ms, far too long to wait for when there's
+// anything else to do, so a reading is split in two: `start` kicks off a
+// conversion and `read` collects it on a later pass round the main loop.
+// If the sensor stops answering it's searched for again on the next
+// `start`, so it can be unplugged and plugged back in.
+
+use core::fmt::Debug;
+
+use ds18b20::{Ds18b20, Resolution};
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use one_wire_bus::OneWire;
+
+/// How long after `start` before `read` has a reading to collect.
+pub const CONVERSION_MS: u32 = 750;
+
+/// The first DS18B20 on the bus, set to 12 bit resolution.
+pub fn find_sensor<P, E>(one_wire_bus: &mut OneWire<P>, delay: &mut (impl DelayUs<u16> + DelayMs<u16>)) -> Option<Ds18b20>
+where
+    P: OutputPin<Error = E> + InputPin<Error = E>,
+    E: Debug,
+{
+    let mut search_state = None;
+    while let Some((address, state)) = one_wire_bus.device_search(search_state.as_ref(), false, delay).ok()? {
+        search_state = Some(state);
+        if address.family_code() != ds18b20::FAMILY_CODE {
+            continue;
+        }
+        let sensor = Ds18b20::new::<E>(address).ok()?;
+        sensor.set_config(i8::MIN, i8::MAX, Resolution::Bits12, one_wire_bus, delay).ok()?;
+        return Some(sensor);
+    }
+    None
+}
+
+#[derive(Default)]
+pub struct Thermometer {
+    sensor: Option<Ds18b20>,
+}
+
+impl Thermometer {
+    pub const fn new() -> Self {
+        Self { sensor: None }
+    }
+
+    /// Starts a conversion, looking for the sensor first if it isn't
+    /// known. Returns false if there's no sensor answering.
+    pub fn start<P, E>(&mut self, one_wire_bus: &mut OneWire<P>, delay: &mut (impl DelayUs<u16> + DelayMs<u16>)) -> bool
+    where
+        P: OutputPin<Error = E> + InputPin<Error = E>,
+        E: Debug,
+    {
+        if self.sensor.is_none() {
+            self.sensor = find_sensor(one_wire_bus, delay);
+        }
+        let started = self
+            .sensor
+            .as_ref()
+            .is_some_and(|sensor| sensor.start_temp_measurement(one_wire_bus, delay).is_ok());
+        if !started {
+            self.sensor = None;
+        }
+        started
+    }
+
+    /// Collects the conversion `start` began, in degrees C, at least
+    /// `CONVERSION_MS` later. `None` if the sensor didn't answer.
+    pub fn read<P, E>(&mut self, one_wire_bus: &mut OneWire<P>, delay: &mut (impl DelayUs<u16> + DelayMs<u16>)) -> Option<f32>
+    where
+        P: OutputPin<Error = E> + InputPin<Error = E>,
+        E: Debug,
+    {
+        let data = self.sensor.as_ref().and_then(|sensor| sensor.read_data(one_wire_bus, delay).ok());
+        if data.is_none() {
+            // Search again next time round in case it was unplugged.
+            self.sensor = None;
+        }
+        data.map(|data| data.temperature)
+    }
+}
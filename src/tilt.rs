@@ -0,0 +1,295 @@
+// The day9 tilt switch as an input: debounced, with hold and shake
+// gestures.
+//
+// The ball in the switch rattles on the contacts for a while every time it
+// moves, so the raw level only counts once it's held steady for the
+// debounce time. Shaking flips the debounced state back and forth, and
+// enough flips close together make a shake. Staying one way up long enough
+// makes a hold, reported once each time.
+//
+// Times are in ms from any fixed starting point and may wrap.
+
+use heapless::Deque;
+
+/// The most flips a shake can be set to need.
+pub const MAX_SHAKE_FLIPS: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TiltConfig {
+    /// How long the switch must read the same to be believed.
+    pub debounce_ms: u32,
+    /// How long it must stay one way up for `Held`.
+    pub hold_ms: u32,
+    /// Flips within `shake_window_ms` that make a shake.
+    pub shake_flips: usize,
+    pub shake_window_ms: u32,
+}
+
+impl Default for TiltConfig {
+    fn default() -> Self {
+        Self {
+            debounce_ms: 20,
+            hold_ms: 2000,
+            shake_flips: 6,
+            shake_window_ms: 1500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Upright,
+    Tilted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiltEvent {
+    Changed(Orientation),
+    /// Has been this way up for the hold time.
+    Held(Orientation),
+    /// Comes instead of the `Changed` for the flip that finished the
+    /// shake, so check `TiltSensor::orientation` for which way up it
+    /// ended.
+    Shake,
+}
+
+pub struct TiltSensor {
+    config: TiltConfig,
+    orientation: Orientation,
+    // `None` until the first reading.
+    since_ms: Option<u32>,
+    held: bool,
+    raw: bool,
+    raw_since_ms: u32,
+    flips: Deque<u32, MAX_SHAKE_FLIPS>,
+}
+
+impl TiltSensor {
+    /// Panics if `shake_flips` is more than `MAX_SHAKE_FLIPS`.
+    pub fn new(config: TiltConfig) -> Self {
+        assert!(config.shake_flips <= MAX_SHAKE_FLIPS, "shake needs too many flips");
+        Self {
+            config,
+            orientation: Orientation::Upright,
+            since_ms: None,
+            held: false,
+            raw: false,
+            raw_since_ms: 0,
+            flips: Deque::new(),
+        }
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// How long it's been this way up.
+    pub fn held_for(&self, now_ms: u32) -> u32 {
+        self.since_ms.map_or(0, |since| now_ms.wrapping_sub(since))
+    }
+
+    /// When `update` next needs calling, even if the switch hasn't moved,
+    /// for the debounce or hold time to run out. `None` if there's nothing
+    /// to wait for, or no reading yet.
+    pub fn deadline_ms(&self) -> Option<u32> {
+        let since = self.since_ms?;
+        let reading = if self.raw { Orientation::Tilted } else { Orientation::Upright };
+        if reading != self.orientation {
+            Some(self.raw_since_ms.wrapping_add(self.config.debounce_ms))
+        } else if !self.held {
+            Some(since.wrapping_add(self.config.hold_ms))
+        } else {
+            None
+        }
+    }
+
+    /// Takes a reading of the switch, closed meaning tilted. Call at least
+    /// a few times per debounce time, or on every edge and by
+    /// `deadline_ms`.
+    pub fn update(&mut self, closed: bool, now_ms: u32) -> Option<TiltEvent> {
+        if self.since_ms.is_none() {
+            self.since_ms = Some(now_ms);
+            self.raw_since_ms = now_ms;
+        }
+        if closed != self.raw {
+            self.raw = closed;
+            self.raw_since_ms = now_ms;
+        }
+
+        let reading = if self.raw { Orientation::Tilted } else { Orientation::Upright };
+        if reading != self.orientation && now_ms.wrapping_sub(self.raw_since_ms) >= self.config.debounce_ms {
+            self.orientation = reading;
+            self.since_ms = Some(now_ms);
+            self.held = false;
+            return Some(self.flip(now_ms).unwrap_or(TiltEvent::Changed(reading)));
+        }
+
+        if !self.held && self.held_for(now_ms) >= self.config.hold_ms {
+            self.held = true;
+            return Some(TiltEvent::Held(self.orientation));
+        }
+        None
+    }
+
+    // Notes a flip and says if it finished a shake.
+    fn flip(&mut self, now_ms: u32) -> Option<TiltEvent> {
+        while self.flips.front().is_some_and(|&at| now_ms.wrapping_sub(at) > self.config.shake_window_ms) {
+            self.flips.pop_front();
+        }
+        if self.flips.is_full() {
+            self.flips.pop_front();
+        }
+        self.flips.push_back(now_ms).ok();
+
+        if self.config.shake_flips > 0 && self.flips.len() >= self.config.shake_flips {
+            self.flips.clear();
+            // No holding on to whichever way up the shake ended.
+            self.held = true;
+            return Some(TiltEvent::Shake);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEBOUNCE_MS: u32 = 20;
+
+    fn sensor() -> TiltSensor {
+        TiltSensor::new(TiltConfig::default())
+    }
+
+    // The switch moving to `closed` at `at_ms` and staying there for the
+    // debounce time, returning what that gave.
+    fn settle(sensor: &mut TiltSensor, closed: bool, at_ms: u32) -> Option<TiltEvent> {
+        assert_eq!(sensor.update(closed, at_ms), None);
+        sensor.update(closed, at_ms.wrapping_add(DEBOUNCE_MS))
+    }
+
+    #[test]
+    fn bounce_shorter_than_the_debounce_is_ignored() {
+        let mut sensor = sensor();
+        assert_eq!(sensor.update(false, 0), None);
+        for (at_ms, closed) in [(5, true), (10, false), (15, true), (30, false), (45, true), (60, false)] {
+            assert_eq!(sensor.update(closed, at_ms), None);
+        }
+        assert_eq!(sensor.update(false, 100), None);
+        assert_eq!(sensor.orientation(), Orientation::Upright);
+
+        assert_eq!(sensor.update(true, 200), None);
+        assert_eq!(sensor.update(true, 219), None);
+        assert_eq!(sensor.update(true, 220), Some(TiltEvent::Changed(Orientation::Tilted)));
+        assert_eq!(sensor.orientation(), Orientation::Tilted);
+    }
+
+    #[test]
+    fn held_fires_once_each_way_up() {
+        let mut sensor = sensor();
+        assert_eq!(sensor.update(false, 0), None);
+        assert_eq!(sensor.update(false, 1_999), None);
+        assert_eq!(sensor.update(false, 2_000), Some(TiltEvent::Held(Orientation::Upright)));
+        assert_eq!(sensor.update(false, 5_000), None);
+
+        assert_eq!(settle(&mut sensor, true, 6_000), Some(TiltEvent::Changed(Orientation::Tilted)));
+        assert_eq!(sensor.held_for(6_520), 500);
+        assert_eq!(sensor.update(true, 8_019), None);
+        assert_eq!(sensor.update(true, 8_020), Some(TiltEvent::Held(Orientation::Tilted)));
+        assert_eq!(sensor.update(true, 20_000), None);
+    }
+
+    #[test]
+    fn flips_close_together_are_a_shake() {
+        let mut sensor = sensor();
+        sensor.update(false, 0);
+        let mut closed = false;
+        for i in 1..=6 {
+            closed = !closed;
+            let event = settle(&mut sensor, closed, i * 100);
+            if i < 6 {
+                let orientation = if closed { Orientation::Tilted } else { Orientation::Upright };
+                assert_eq!(event, Some(TiltEvent::Changed(orientation)), "flip {i}");
+            } else {
+                // In place of the `Changed` for the last flip.
+                assert_eq!(event, Some(TiltEvent::Shake));
+            }
+        }
+        assert_eq!(sensor.orientation(), Orientation::Upright);
+
+        // No hold for however it ended up after a shake.
+        assert_eq!(sensor.deadline_ms(), None);
+        assert_eq!(sensor.update(false, 10_000), None);
+
+        // And it starts counting again from nothing.
+        assert!(matches!(settle(&mut sensor, true, 11_000), Some(TiltEvent::Changed(_))));
+    }
+
+    #[test]
+    fn flips_outside_the_window_dont_count() {
+        let mut sensor = sensor();
+        sensor.update(false, 0);
+        let mut closed = false;
+        // Six flips 2 s from first to last, never six within 1.5 s.
+        for i in 0..12 {
+            closed = !closed;
+            let event = settle(&mut sensor, closed, i * 400);
+            assert!(matches!(event, Some(TiltEvent::Changed(_))), "flip {i}: {event:?}");
+        }
+    }
+
+    #[test]
+    fn deadlines() {
+        let mut sensor = sensor();
+        assert_eq!(sensor.deadline_ms(), None);
+
+        // Waiting to be held.
+        assert_eq!(sensor.update(false, 100), None);
+        assert_eq!(sensor.deadline_ms(), Some(2_100));
+
+        // Waiting for the debounce.
+        assert_eq!(sensor.update(true, 200), None);
+        assert_eq!(sensor.deadline_ms(), Some(220));
+        // Bouncing back to where it was is back to waiting for the hold.
+        assert_eq!(sensor.update(false, 205), None);
+        assert_eq!(sensor.deadline_ms(), Some(2_100));
+        assert_eq!(sensor.update(true, 210), None);
+        assert_eq!(sensor.deadline_ms(), Some(230));
+
+        assert_eq!(sensor.update(true, 230), Some(TiltEvent::Changed(Orientation::Tilted)));
+        assert_eq!(sensor.deadline_ms(), Some(2_230));
+        assert_eq!(sensor.update(true, 2_230), Some(TiltEvent::Held(Orientation::Tilted)));
+        assert_eq!(sensor.deadline_ms(), None);
+    }
+
+    #[test]
+    fn timestamps_wrap() {
+        let start = u32::MAX - 10;
+        let mut sensor = sensor();
+        assert_eq!(sensor.update(false, start), None);
+
+        // Broken before the wrap, debounced after it.
+        assert_eq!(sensor.update(true, u32::MAX - 5), None);
+        assert_eq!(sensor.deadline_ms(), Some(14));
+        assert_eq!(sensor.update(true, 13), None);
+        assert_eq!(sensor.update(true, 14), Some(TiltEvent::Changed(Orientation::Tilted)));
+
+        assert_eq!(sensor.held_for(1_014), 1_000);
+        assert_eq!(sensor.update(true, 2_013), None);
+        assert_eq!(sensor.update(true, 2_014), Some(TiltEvent::Held(Orientation::Tilted)));
+    }
+
+    #[test]
+    fn shakes_across_the_wrap() {
+        let mut sensor = sensor();
+        let start = u32::MAX - 300;
+        sensor.update(false, start);
+        let mut closed = false;
+        let mut last = None;
+        for i in 1..=6 {
+            closed = !closed;
+            last = settle(&mut sensor, closed, start.wrapping_add(i * 100));
+        }
+        assert_eq!(last, Some(TiltEvent::Shake));
+    }
+}
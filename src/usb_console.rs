@@ -0,0 +1,108 @@
+// Buffered text output over a USB CDC serial port.
+//
+// `SerialPort::write` only accepts as much as fits in the endpoint buffer
+// and fails with `WouldBlock` while the host isn't reading, so writing to it
+// directly loses data. `UsbConsole` queues everything into a ring buffer and
+// drains it a packet at a time whenever the device is polled. Bytes that
+// don't fit in the ring buffer are dropped and counted.
+
+use core::fmt;
+
+use heapless::Deque;
+use usb_device::class_prelude::UsbBus;
+use usb_device::device::UsbDevice;
+use usb_device::UsbError;
+use usbd_serial::SerialPort;
+
+pub struct UsbConsole<'a, B: UsbBus, const N: usize> {
+    serial: SerialPort<'a, B>,
+    buf: Deque<u8, N>,
+    dropped: u32,
+}
+
+impl<'a, B: UsbBus, const N: usize> UsbConsole<'a, B, N> {
+    pub fn new(serial: SerialPort<'a, B>) -> Self {
+        Self {
+            serial,
+            buf: Deque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Services the device and pushes out as much buffered data as the host
+    /// will take. Call from the USB interrupt or as often as possible.
+    /// Returns true if the serial class had an event, in which case there
+    /// may be data to `read`.
+    pub fn poll(&mut self, usb_dev: &mut UsbDevice<'a, B>) -> bool {
+        let event = usb_dev.poll(&mut [&mut self.serial]);
+        self.flush();
+        event
+    }
+
+    /// Queues bytes for sending, returning how many were accepted.
+    pub fn write_bytes(&mut self, data: &[u8]) -> usize {
+        let mut accepted = 0;
+        for &byte in data {
+            if self.buf.push_back(byte).is_err() {
+                break;
+            }
+            accepted += 1;
+        }
+        self.dropped = self.dropped.saturating_add((data.len() - accepted) as u32);
+        accepted
+    }
+
+    pub fn read(&mut self, data: &mut [u8]) -> usize {
+        self.serial.read(data).unwrap_or(0)
+    }
+
+    /// Number of bytes thrown away because the buffer was full.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    pub fn reset_dropped(&mut self) {
+        self.dropped = 0;
+    }
+
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn serial(&mut self) -> &mut SerialPort<'a, B> {
+        &mut self.serial
+    }
+
+    /// Sends as much buffered data as the endpoint will take. `poll` does
+    /// this already, call it directly when polling the device yourself
+    /// alongside other classes.
+    pub fn flush(&mut self) {
+        while !self.buf.is_empty() {
+            let chunk = self.buf.as_slices().0;
+            let len = chunk.len();
+            match self.serial.write(chunk) {
+                Ok(written) => {
+                    for _ in 0..written {
+                        self.buf.pop_front();
+                    }
+                    // Partial write, the endpoint is full until the next poll.
+                    if written < len {
+                        break;
+                    }
+                }
+                Err(UsbError::WouldBlock) => break,
+                // Not configured yet or the host went away, keep the data.
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl<'a, B: UsbBus, const N: usize> fmt::Write for UsbConsole<'a, B, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Never report an error, drops are tracked in `dropped` instead so
+        // a full buffer doesn't abort the rest of a `write!`.
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
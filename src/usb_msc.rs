@@ -0,0 +1,330 @@
+// Minimal read-only USB mass storage class (bulk-only transport, SCSI
+// transparent command set) for usb-device 0.2.
+//
+// Only the commands hosts actually send to a small removable disk are
+// implemented. The medium always reports as write protected and WRITE(10)
+// fails with a DATA PROTECT sense so hosts mount it read-only.
+
+use usb_device::class_prelude::*;
+use usb_device::Result;
+
+const CLASS_MSC: u8 = 0x08;
+const SUBCLASS_SCSI: u8 = 0x06;
+const PROTOCOL_BBB: u8 = 0x50;
+
+const REQ_GET_MAX_LUN: u8 = 0xFE;
+const REQ_BULK_ONLY_RESET: u8 = 0xFF;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CBW_LEN: usize = 31;
+const CSW_LEN: usize = 13;
+
+const PACKET_SIZE: u16 = 64;
+pub const BLOCK_SIZE: usize = 512;
+
+const STATUS_PASSED: u8 = 0;
+const STATUS_FAILED: u8 = 1;
+
+const SENSE_NONE: (u8, u8) = (0x00, 0x00);
+const SENSE_INVALID_COMMAND: (u8, u8) = (0x05, 0x20);
+const SENSE_LBA_OUT_OF_RANGE: (u8, u8) = (0x05, 0x21);
+const SENSE_WRITE_PROTECTED: (u8, u8) = (0x07, 0x27);
+const SENSE_READ_ERROR: (u8, u8) = (0x03, 0x11);
+const SENSE_MEDIUM_CHANGED: (u8, u8) = (0x06, 0x28);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadError;
+
+/// Something that can be presented as the disk's contents.
+pub trait BlockDevice {
+    fn block_count(&self) -> u32;
+
+    fn read_block(&mut self, lba: u32, buf: &mut [u8; BLOCK_SIZE]) -> core::result::Result<(), ReadError>;
+
+    /// Called on USB bus reset, a good point to snapshot changing data
+    /// since the host will re-read everything.
+    fn reset(&mut self) {}
+
+    /// Called each time the host polls with TEST UNIT READY. Returning
+    /// true, after switching to the new contents, tells the host the
+    /// medium has changed so it drops what it cached.
+    fn changed(&mut self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Command,
+    DataIn,
+    Status,
+}
+
+pub struct MscClass<'a, B: UsbBus, D: BlockDevice> {
+    iface: InterfaceNumber,
+    read_ep: EndpointOut<'a, B>,
+    write_ep: EndpointIn<'a, B>,
+    device: D,
+
+    state: State,
+    tag: u32,
+    // bytes the host asked for, minus what has been sent
+    residue: u32,
+    status: u8,
+    sense: (u8, u8),
+
+    buf: [u8; BLOCK_SIZE],
+    buf_len: usize,
+    buf_pos: usize,
+    next_lba: u32,
+    blocks_left: u32,
+    // data phase hasn't been ended by a short packet yet
+    send_zlp: bool,
+}
+
+impl<'a, B: UsbBus, D: BlockDevice> MscClass<'a, B, D> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>, device: D) -> Self {
+        Self {
+            iface: alloc.interface(),
+            read_ep: alloc.bulk(PACKET_SIZE),
+            write_ep: alloc.bulk(PACKET_SIZE),
+            device,
+            state: State::Command,
+            tag: 0,
+            residue: 0,
+            status: STATUS_PASSED,
+            sense: SENSE_NONE,
+            buf: [0; BLOCK_SIZE],
+            buf_len: 0,
+            buf_pos: 0,
+            next_lba: 0,
+            blocks_left: 0,
+            send_zlp: false,
+        }
+    }
+
+    pub fn device(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    fn read_command(&mut self) {
+        let mut cbw = [0u8; 64];
+        let len = match self.read_ep.read(&mut cbw) {
+            Ok(len) => len,
+            Err(_) => return,
+        };
+        if len != CBW_LEN || u32::from_le_bytes([cbw[0], cbw[1], cbw[2], cbw[3]]) != CBW_SIGNATURE {
+            // Not a valid CBW, wait for the host to reset us.
+            self.read_ep.stall();
+            self.write_ep.stall();
+            return;
+        }
+
+        self.tag = u32::from_le_bytes([cbw[4], cbw[5], cbw[6], cbw[7]]);
+        self.residue = u32::from_le_bytes([cbw[8], cbw[9], cbw[10], cbw[11]]);
+        let data_in = cbw[12] & 0x80 != 0;
+        let cb_len = (cbw[14] & 0x1F) as usize;
+        let mut cb = [0u8; 16];
+        cb[..cb_len.min(16)].copy_from_slice(&cbw[15..15 + cb_len.min(16)]);
+
+        self.buf_len = 0;
+        self.buf_pos = 0;
+        self.blocks_left = 0;
+        self.status = STATUS_PASSED;
+        self.execute(&cb);
+
+        if !data_in && self.residue > 0 {
+            // Host wants to send data we don't accept (only writes do this).
+            self.read_ep.stall();
+        }
+
+        self.state = if data_in && self.residue > 0 {
+            State::DataIn
+        } else {
+            State::Status
+        };
+        self.send_zlp = true;
+        self.send();
+    }
+
+    fn execute(&mut self, cb: &[u8; 16]) {
+        let blocks = self.device.block_count();
+
+        match cb[0] {
+            // TEST UNIT READY
+            0x00 => {
+                if self.device.changed() {
+                    self.fail(SENSE_MEDIUM_CHANGED);
+                }
+            }
+            // PREVENT ALLOW MEDIUM REMOVAL, START STOP UNIT, VERIFY(10)
+            0x1E | 0x1B | 0x2F => {}
+            // REQUEST SENSE
+            0x03 => {
+                let (key, asc) = self.sense;
+                self.respond(&[0x70, 0, key, 0, 0, 0, 0, 10, 0, 0, 0, 0, asc, 0, 0, 0, 0, 0]);
+                self.sense = SENSE_NONE;
+            }
+            // INQUIRY
+            0x12 => {
+                let mut data = [0u8; 36];
+                data[1] = 0x80; // removable
+                data[2] = 0x04; // SPC-2
+                data[3] = 0x02; // response data format
+                data[4] = 31; // additional length
+                data[8..16].copy_from_slice(b"Codemas ");
+                data[16..32].copy_from_slice(b"Data Logger     ");
+                data[32..36].copy_from_slice(b"0.1 ");
+                self.respond(&data);
+            }
+            // MODE SENSE(6), write protect bit set
+            0x1A => self.respond(&[3, 0, 0x80, 0]),
+            // MODE SENSE(10)
+            0x5A => self.respond(&[0, 6, 0, 0x80, 0, 0, 0, 0]),
+            // READ FORMAT CAPACITIES
+            0x23 => {
+                let mut data = [0u8; 12];
+                data[3] = 8;
+                data[4..8].copy_from_slice(&blocks.to_be_bytes());
+                data[8] = 0x02; // formatted media
+                data[9..12].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes()[1..]);
+                self.respond(&data);
+            }
+            // READ CAPACITY(10)
+            0x25 => {
+                let mut data = [0u8; 8];
+                data[0..4].copy_from_slice(&blocks.saturating_sub(1).to_be_bytes());
+                data[4..8].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes());
+                self.respond(&data);
+            }
+            // READ(10)
+            0x28 => {
+                let lba = u32::from_be_bytes([cb[2], cb[3], cb[4], cb[5]]);
+                let count = u16::from_be_bytes([cb[7], cb[8]]) as u32;
+                if lba.checked_add(count).is_none_or(|end| end > blocks) {
+                    self.fail(SENSE_LBA_OUT_OF_RANGE);
+                } else {
+                    self.next_lba = lba;
+                    self.blocks_left = count;
+                }
+            }
+            // WRITE(10)
+            0x2A => self.fail(SENSE_WRITE_PROTECTED),
+            _ => self.fail(SENSE_INVALID_COMMAND),
+        }
+    }
+
+    fn respond(&mut self, data: &[u8]) {
+        self.buf[..data.len()].copy_from_slice(data);
+        self.buf_len = data.len();
+    }
+
+    fn fail(&mut self, sense: (u8, u8)) {
+        self.status = STATUS_FAILED;
+        self.sense = sense;
+    }
+
+    // Pushes out the next packet of whatever phase we're in. Called again
+    // each time the IN endpoint finishes a transfer.
+    fn send(&mut self) {
+        if self.state == State::DataIn {
+            if self.buf_pos == self.buf_len && self.blocks_left > 0 && self.residue > 0 {
+                if self.device.read_block(self.next_lba, &mut self.buf).is_err() {
+                    self.fail(SENSE_READ_ERROR);
+                    self.blocks_left = 0;
+                    self.buf_len = 0;
+                } else {
+                    self.next_lba += 1;
+                    self.blocks_left -= 1;
+                    self.buf_len = BLOCK_SIZE;
+                }
+                self.buf_pos = 0;
+            }
+
+            // Never send more than the host asked for.
+            let end = self.buf_len.min(self.buf_pos + self.residue as usize);
+            if self.buf_pos < end {
+                let chunk = (end - self.buf_pos).min(PACKET_SIZE as usize);
+                if let Ok(written) = self.write_ep.write(&self.buf[self.buf_pos..self.buf_pos + chunk]) {
+                    self.buf_pos += written;
+                    self.residue -= written as u32;
+                    self.send_zlp = written == PACKET_SIZE as usize;
+                }
+                return;
+            }
+
+            // Out of data. If the host expected more and the last packet was
+            // full sized (or nothing was sent) it needs a zero length packet
+            // to see the end of the data phase.
+            if self.residue > 0 && self.send_zlp {
+                if self.write_ep.write(&[]).is_ok() {
+                    self.state = State::Status;
+                }
+                return;
+            }
+            self.state = State::Status;
+        }
+
+        if self.state == State::Status {
+            let mut csw = [0u8; CSW_LEN];
+            csw[0..4].copy_from_slice(&CSW_SIGNATURE.to_le_bytes());
+            csw[4..8].copy_from_slice(&self.tag.to_le_bytes());
+            csw[8..12].copy_from_slice(&self.residue.to_le_bytes());
+            csw[12] = self.status;
+            if self.write_ep.write(&csw).is_ok() {
+                self.state = State::Command;
+            }
+        }
+    }
+}
+
+impl<B: UsbBus, D: BlockDevice> UsbClass<B> for MscClass<'_, B, D> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.interface(self.iface, CLASS_MSC, SUBCLASS_SCSI, PROTOCOL_BBB)?;
+        writer.endpoint(&self.read_ep)?;
+        writer.endpoint(&self.write_ep)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.state = State::Command;
+        self.sense = SENSE_NONE;
+        self.device.reset();
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = xfer.request();
+        if req.request_type == control::RequestType::Class
+            && req.recipient == control::Recipient::Interface
+            && req.index == u8::from(self.iface) as u16
+            && req.request == REQ_GET_MAX_LUN
+        {
+            xfer.accept_with(&[0]).ok();
+        }
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = xfer.request();
+        if req.request_type == control::RequestType::Class
+            && req.recipient == control::Recipient::Interface
+            && req.index == u8::from(self.iface) as u16
+            && req.request == REQ_BULK_ONLY_RESET
+        {
+            self.state = State::Command;
+            xfer.accept().ok();
+        }
+    }
+
+    fn endpoint_out(&mut self, addr: EndpointAddress) {
+        if addr == self.read_ep.address() && self.state == State::Command {
+            self.read_command();
+        }
+    }
+
+    fn endpoint_in_complete(&mut self, addr: EndpointAddress) {
+        if addr == self.write_ep.address() {
+            self.send();
+        }
+    }
+}
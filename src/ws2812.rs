@@ -0,0 +1,430 @@
+// The WS2812 wire format, kept apart from the PIO driver so it can be
+// checked on the host.
+//
+// Each pixel is sent as 8 bits per channel, most significant bit first, in
+// whatever order the chip expects. For the PIO the channels are packed into
+// a 32 bit word starting at bit 31, which the state machine shifts out to
+// the left, `bits()` bits per pixel.
+//
+// The chips differ in colour order, speed and how long the line should be
+// high for each bit, all of which `Chipset` covers. The PIO program is
+// built for the chipset here too, so `measure` can run it through the
+// simulator and check the pulses it makes against the datasheet.
+
+use pio::{Assembler, JmpCondition, OutDestination, Program, SideSet, RP2040_MAX_PROGRAM_SIZE};
+use rgb::RGB8;
+use rp_pico::hal::pio::ShiftDirection;
+
+use crate::color;
+use crate::pio_sim::{self, Machine};
+
+/// Bits per second on the data line.
+pub const BIT_RATE: u32 = 800_000;
+
+/// Older WS2811 parts run at half speed.
+pub const SLOW_BIT_RATE: u32 = 400_000;
+
+/// A PIO clock divisor, and how far off the bit rate it gives is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockDivisor {
+    pub int: u16,
+    /// In 256ths.
+    pub frac: u8,
+    /// Actual bit rate less the one asked for, in parts per million.
+    pub error_ppm: i32,
+}
+
+impl ClockDivisor {
+    /// The closest divisor to run a program taking `cycles_per_bit` state
+    /// machine cycles per bit at `bit_rate`, with the system clock at
+    /// `clock_hz`. `None` if the clock is too slow or too fast to get
+    /// there.
+    pub fn new(clock_hz: u32, cycles_per_bit: u32, bit_rate: u32) -> Option<Self> {
+        let cycle_rate = cycles_per_bit as u64 * bit_rate as u64;
+        if cycle_rate == 0 {
+            return None;
+        }
+        // Fixed point with 8 fractional bits, rounded to nearest.
+        let divisor = (clock_hz as u64 * 256 + cycle_rate / 2) / cycle_rate;
+        if !(256..=0xFF_FFFF).contains(&divisor) {
+            return None;
+        }
+        let actual_ppm = clock_hz as u64 * 256 * 1_000_000 / (divisor * cycle_rate);
+        Some(Self {
+            int: (divisor >> 8) as u16,
+            frac: divisor as u8,
+            error_ppm: actual_ppm as i32 - 1_000_000,
+        })
+    }
+
+    /// As `clock_divisor_fixed_point` takes it.
+    pub fn fixed_point(self) -> (u16, u8) {
+        (self.int, self.frac)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorOrder {
+    /// WS2812 and WS2812B, what the calendar strip uses.
+    Grb,
+    /// Some WS2811 strips.
+    Rgb,
+    /// SK6812 RGBW.
+    Grbw,
+}
+
+impl ColorOrder {
+    /// Bits sent per pixel.
+    pub const fn bits(self) -> u8 {
+        match self {
+            ColorOrder::Grb | ColorOrder::Rgb => 24,
+            ColorOrder::Grbw => 32,
+        }
+    }
+
+    /// Packs a pixel into a word for the PIO, first bit to send in bit 31.
+    /// `white` is only sent for `Grbw`.
+    pub fn pack(self, color: RGB8, white: u8) -> u32 {
+        let [r, g, b, w] = [color.r, color.g, color.b, white].map(u32::from);
+        match self {
+            ColorOrder::Grb => g << 24 | r << 16 | b << 8,
+            ColorOrder::Rgb => r << 24 | g << 16 | b << 8,
+            ColorOrder::Grbw => g << 24 | r << 16 | b << 8 | w,
+        }
+    }
+
+    /// The bits a packed word puts on the data line, in order.
+    pub fn bit_stream(self, word: u32) -> impl Iterator<Item = bool> {
+        (0..self.bits()).map(move |i| word & (1 << (31 - i)) != 0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chipset {
+    /// What the calendar strip uses.
+    Ws2812b,
+    /// RGBW, the white LED takes over whatever grey is in each colour.
+    Sk6812Rgbw,
+    /// In its 800 kHz mode.
+    Ws2811,
+    /// In its 400 kHz mode.
+    Ws2811Slow,
+}
+
+/// Datasheet limits in ns, (min, max): high time for a 0 bit, for a 1 bit,
+/// and the whole bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub t0h: (u64, u64),
+    pub t1h: (u64, u64),
+    pub period: (u64, u64),
+}
+
+impl Chipset {
+    pub const fn order(self) -> ColorOrder {
+        match self {
+            Chipset::Ws2812b => ColorOrder::Grb,
+            Chipset::Sk6812Rgbw => ColorOrder::Grbw,
+            Chipset::Ws2811 | Chipset::Ws2811Slow => ColorOrder::Rgb,
+        }
+    }
+
+    pub const fn bit_rate(self) -> u32 {
+        match self {
+            Chipset::Ws2811Slow => SLOW_BIT_RATE,
+            _ => BIT_RATE,
+        }
+    }
+
+    pub const fn limits(self) -> Limits {
+        match self {
+            Chipset::Ws2812b => Limits { t0h: (250, 550), t1h: (650, 950), period: (650, 1850) },
+            Chipset::Sk6812Rgbw => Limits { t0h: (150, 450), t1h: (450, 750), period: (650, 1850) },
+            Chipset::Ws2811 => Limits { t0h: (175, 325), t1h: (525, 675), period: (1100, 1400) },
+            Chipset::Ws2811Slow => Limits { t0h: (350, 650), t1h: (1050, 1350), period: (2200, 2800) },
+        }
+    }
+
+    // State machine cycles each bit starts high for, stays high for after
+    // that if it's a 1, and is low for at the end. Each adds up to 10.
+    const fn phases(self) -> (u8, u8, u8) {
+        match self {
+            Chipset::Ws2812b => (2, 5, 3),
+            Chipset::Sk6812Rgbw => (3, 2, 5),
+            Chipset::Ws2811 | Chipset::Ws2811Slow => (2, 3, 5),
+        }
+    }
+
+    /// State machine cycles `program` takes per bit.
+    pub const fn cycles_per_bit(self) -> u32 {
+        let (high, one, low) = self.phases();
+        (high + one + low) as u32
+    }
+
+    /// The divisor to run `program` at the bit rate with the system clock
+    /// at `clock_hz`.
+    pub fn clock_divisor(self, clock_hz: u32) -> Option<ClockDivisor> {
+        ClockDivisor::new(clock_hz, self.cycles_per_bit(), self.bit_rate())
+    }
+
+    /// Packs a pixel into a word for the PIO, taking the white out of it
+    /// first for RGBW chips.
+    pub fn pack(self, color: RGB8) -> u32 {
+        match self.order() {
+            ColorOrder::Grbw => {
+                let (color, white) = color::split_white(color);
+                ColorOrder::Grbw.pack(color, white)
+            }
+            order => order.pack(color, 0),
+        }
+    }
+
+    /// Sends one bit per `cycles_per_bit` cycles, shifted out of the OSR to
+    /// the left, on the side-set pin. The line is held low while the FIFO
+    /// is empty, which is what latches the strip.
+    pub fn program(self) -> Program<RP2040_MAX_PROGRAM_SIZE> {
+        let (high, one, low) = self.phases();
+        let mut a = Assembler::new_with_side_set(SideSet::new(false, 1, false));
+        let mut wrap_target = a.label();
+        let mut wrap_source = a.label();
+        let mut do_zero = a.label();
+        a.bind(&mut wrap_target);
+        a.out_with_delay_and_side_set(OutDestination::X, 1, low - 1, 0);
+        a.jmp_with_delay_and_side_set(JmpCondition::XIsZero, &mut do_zero, high - 1, 1);
+        a.jmp_with_delay_and_side_set(JmpCondition::Always, &mut wrap_target, one - 1, 1);
+        a.bind(&mut do_zero);
+        a.nop_with_delay_and_side_set(one - 1, 0);
+        a.bind(&mut wrap_source);
+        a.assemble_with_wrap(wrap_source, wrap_target)
+    }
+}
+
+/// Pulse widths seen on the data line, (shortest, longest) in ns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub t0h: (u64, u64),
+    pub t1h: (u64, u64),
+    pub period: (u64, u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingError {
+    Simulator(pio_sim::Error),
+    /// There weren't the same number of pulses as bits sent, or the line
+    /// didn't end up low.
+    WrongBits,
+    /// Outside the datasheet limits, with the worst value seen.
+    T0H(u64),
+    T1H(u64),
+    Period(u64),
+}
+
+impl From<pio_sim::Error> for TimingError {
+    fn from(e: pio_sim::Error) -> Self {
+        TimingError::Simulator(e)
+    }
+}
+
+impl Timing {
+    /// Checks every pulse is within the datasheet limits for `chipset`.
+    pub fn check(&self, chipset: Chipset) -> Result<(), TimingError> {
+        let limits = chipset.limits();
+        let within = |(min, max): (u64, u64), (low, high): (u64, u64)| {
+            if min < low {
+                Some(min)
+            } else if max > high {
+                Some(max)
+            } else {
+                None
+            }
+        };
+        if let Some(ns) = within(self.t0h, limits.t0h) {
+            return Err(TimingError::T0H(ns));
+        }
+        if let Some(ns) = within(self.t1h, limits.t1h) {
+            return Err(TimingError::T1H(ns));
+        }
+        if let Some(ns) = within(self.period, limits.period) {
+            return Err(TimingError::Period(ns));
+        }
+        Ok(())
+    }
+}
+
+/// Runs the chipset's program in the simulator set up the way
+/// `NeoPixelStrip` sets up the state machine, sends a couple of pixels with
+/// both bit values in every position and measures the pulses for each
+/// value. `clock_hz` is the system clock.
+pub fn measure(chipset: Chipset, clock_hz: u32, clock_divisor: (u16, u8)) -> Result<Timing, TimingError> {
+    let order = chipset.order();
+    let program = chipset.program();
+    let config = pio_sim::Config {
+        clock_divisor,
+        out_shift_direction: ShiftDirection::Left,
+        autopull: true,
+        pull_threshold: order.bits() % 32,
+        ..Default::default()
+    };
+    let pixels = [RGB8::new(0xA5, 0x5A, 0xF0), RGB8::new(0x5A, 0xA5, 0x0F)];
+    let words = pixels.map(|pixel| order.pack(pixel, 0x3C));
+    let mut expected = words.iter().flat_map(|&word| order.bit_stream(word));
+
+    let mut machine = Machine::new(&program, config, words.into_iter());
+    let mut timing = Timing { t0h: (u64::MAX, 0), t1h: (u64::MAX, 0), period: (u64::MAX, 0) };
+    let mut rose = None;
+    let mut extra = false;
+    let widen = |(min, max): (u64, u64), ns: u64| (min.min(ns), max.max(ns));
+    // Twice what the pixels take, it should stall long before then.
+    let max_cycles = 2 * chipset.cycles_per_bit() as u64 * 2 * order.bits() as u64;
+    machine.run(max_cycles, |cycle, pins| {
+        if pins & 1 != 0 {
+            if let Some(start) = rose {
+                timing.period = widen(timing.period, config.ns(cycle - start, clock_hz));
+            }
+            rose = Some(cycle);
+        } else if let Some(start) = rose {
+            let high = config.ns(cycle - start, clock_hz);
+            match expected.next() {
+                Some(true) => timing.t1h = widen(timing.t1h, high),
+                Some(false) => timing.t0h = widen(timing.t0h, high),
+                None => extra = true,
+            }
+        }
+    })?;
+
+    if extra || expected.next().is_some() || !machine.stalled() || machine.pins() & 1 != 0 {
+        return Err(TimingError::WrongBits);
+    }
+    Ok(timing)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    fn bits(order: ColorOrder, word: u32) -> Vec<u8> {
+        order.bit_stream(word).map(u8::from).collect()
+    }
+
+    #[test]
+    fn grb_sends_green_first() {
+        let word = ColorOrder::Grb.pack(RGB8::new(0x11, 0x22, 0x33), 0x44);
+        assert_eq!(word, 0x2211_3300);
+    }
+
+    #[test]
+    fn rgb_sends_red_first() {
+        let word = ColorOrder::Rgb.pack(RGB8::new(0x11, 0x22, 0x33), 0x44);
+        assert_eq!(word, 0x1122_3300);
+    }
+
+    #[test]
+    fn grbw_sends_white_last() {
+        let word = ColorOrder::Grbw.pack(RGB8::new(0x11, 0x22, 0x33), 0x44);
+        assert_eq!(word, 0x2211_3344);
+    }
+
+    #[test]
+    fn bit_stream_is_most_significant_bit_first() {
+        // Pure green on a GRB chip is eight ones and then sixteen zeros.
+        let word = ColorOrder::Grb.pack(RGB8::new(0, 0xFF, 0), 0);
+        let mut expected = [0; 24];
+        expected[..8].fill(1);
+        assert_eq!(bits(ColorOrder::Grb, word), expected);
+
+        // 0x80 red is a single one at the top of the second byte.
+        let word = ColorOrder::Grb.pack(RGB8::new(0x80, 0, 0), 0);
+        let mut expected = [0; 24];
+        expected[8] = 1;
+        assert_eq!(bits(ColorOrder::Grb, word), expected);
+
+        // And 0x01 blue one at the very end.
+        let word = ColorOrder::Rgb.pack(RGB8::new(0, 0, 0x01), 0);
+        let mut expected = [0; 24];
+        expected[23] = 1;
+        assert_eq!(bits(ColorOrder::Rgb, word), expected);
+    }
+
+    #[test]
+    fn bit_stream_length_follows_the_order() {
+        assert_eq!(ColorOrder::Grb.bit_stream(u32::MAX).count(), 24);
+        assert_eq!(ColorOrder::Rgb.bit_stream(u32::MAX).count(), 24);
+        assert_eq!(ColorOrder::Grbw.bit_stream(u32::MAX).count(), 32);
+
+        // The white byte is sent last, after blue.
+        let word = ColorOrder::Grbw.pack(RGB8::new(0, 0, 0), 0x01);
+        let mut expected = [0; 32];
+        expected[31] = 1;
+        assert_eq!(bits(ColorOrder::Grbw, word), expected);
+    }
+
+    #[test]
+    fn sk6812_takes_the_white_out() {
+        let chipset = Chipset::Sk6812Rgbw;
+        assert_eq!(chipset.pack(RGB8::new(0xFF, 0xFF, 0xFF)), 0x0000_00FF);
+        assert_eq!(chipset.pack(RGB8::new(0x30, 0x50, 0x20)), 0x3010_0020);
+        assert_eq!(chipset.pack(RGB8::new(0xFF, 0, 0)), 0x00FF_0000);
+        assert_eq!(chipset.pack(RGB8::new(0, 0, 0)), 0);
+    }
+
+    #[test]
+    fn other_chipsets_leave_the_white_in() {
+        let white = RGB8::new(0xFF, 0xFF, 0xFF);
+        assert_eq!(Chipset::Ws2812b.pack(white), 0xFFFF_FF00);
+        assert_eq!(Chipset::Ws2811.pack(RGB8::new(0x30, 0x50, 0x20)), 0x3050_2000);
+        assert_eq!(Chipset::Ws2811Slow.pack(RGB8::new(0x30, 0x50, 0x20)), 0x3050_2000);
+        assert_eq!(Chipset::Ws2812b.pack(RGB8::new(0x30, 0x50, 0x20)), 0x5030_2000);
+    }
+
+    const CLOCK_HZ: u32 = 125_000_000;
+
+    fn measure_at(chipset: Chipset, clock_hz: u32) -> Result<Timing, TimingError> {
+        let divisor = chipset.clock_divisor(clock_hz).unwrap();
+        measure(chipset, clock_hz, divisor.fixed_point())
+    }
+
+    #[test]
+    fn every_chipset_is_within_limits_at_125_mhz() {
+        for chipset in [Chipset::Ws2812b, Chipset::Sk6812Rgbw, Chipset::Ws2811, Chipset::Ws2811Slow] {
+            let timing = measure_at(chipset, CLOCK_HZ).unwrap();
+            assert_eq!(timing.check(chipset), Ok(()), "{chipset:?} {timing:?}");
+        }
+    }
+
+    #[test]
+    fn divisor_is_close_at_125_mhz() {
+        // 125 MHz / (10 cycles * 800 kHz) = 15.625, which is exact.
+        let divisor = Chipset::Ws2812b.clock_divisor(CLOCK_HZ).unwrap();
+        assert_eq!(divisor, ClockDivisor { int: 15, frac: 160, error_ppm: 0 });
+        let divisor = Chipset::Ws2811Slow.clock_divisor(CLOCK_HZ).unwrap();
+        assert_eq!(divisor.fixed_point(), (31, 64));
+    }
+
+    #[test]
+    fn wrong_divisor_fails_the_check() {
+        // The slow WS2811 divisor runs a WS2812B at half speed.
+        let slow = Chipset::Ws2811Slow.clock_divisor(CLOCK_HZ).unwrap();
+        let timing = measure(Chipset::Ws2812b, CLOCK_HZ, slow.fixed_point()).unwrap();
+        assert_eq!(timing.check(Chipset::Ws2812b), Err(TimingError::T1H(1750)));
+
+        // And the other way round, far too fast.
+        let fast = Chipset::Ws2812b.clock_divisor(CLOCK_HZ).unwrap();
+        let timing = measure(Chipset::Ws2811Slow, CLOCK_HZ, fast.fixed_point()).unwrap();
+        assert!(timing.check(Chipset::Ws2811Slow).is_err(), "{timing:?}");
+    }
+
+    #[test]
+    fn wrong_clock_fails_the_check() {
+        // A divisor worked out for 125 MHz with the clock raised to 200 MHz.
+        let divisor = Chipset::Ws2811.clock_divisor(CLOCK_HZ).unwrap();
+        let timing = measure(Chipset::Ws2811, 200_000_000, divisor.fixed_point()).unwrap();
+        assert!(timing.check(Chipset::Ws2811).is_err(), "{timing:?}");
+
+        // Whereas 133 MHz is still within the WS2811's limits.
+        let timing = measure(Chipset::Ws2811, 133_000_000, divisor.fixed_point()).unwrap();
+        assert_eq!(timing.check(Chipset::Ws2811), Ok(()));
+    }
+}